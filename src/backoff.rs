@@ -0,0 +1,47 @@
+use rand::Rng;
+use std::{cmp::min, time::Duration};
+
+const BASE: Duration = Duration::from_millis(500);
+const CAP: Duration = Duration::from_secs(30);
+
+/// Decorrelated-jitter backoff: each delay is drawn uniformly from
+/// `[base, previous_delay * 3)`, capped at `cap`. Spreads out retries across
+/// many concurrent tiles better than a flat or plain exponential delay,
+/// avoiding synchronized retry storms against the tile server.
+///
+/// See <https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/>.
+#[derive(Debug)]
+pub struct DecorrelatedJitter {
+    delay: Duration,
+}
+
+impl DecorrelatedJitter {
+    pub fn new() -> Self {
+        Self { delay: BASE }
+    }
+
+    /// Computes the next backoff delay and advances internal state.
+    pub fn next(&mut self) -> Duration {
+        let upper = (self.delay * 3).max(BASE);
+        let secs = rand::thread_rng().gen_range(BASE.as_secs_f64(), upper.as_secs_f64());
+
+        self.delay = min(Duration::from_secs_f64(secs), CAP);
+        self.delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_never_go_below_base_or_above_cap() {
+        let mut backoff = DecorrelatedJitter::new();
+
+        for _ in 0..1000 {
+            let delay = backoff.next();
+            assert!(delay >= BASE);
+            assert!(delay <= CAP);
+        }
+    }
+}
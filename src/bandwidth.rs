@@ -0,0 +1,24 @@
+use crate::token_bucket::TokenBucket;
+
+/// A shared token-bucket limiter capping aggregate throughput across all
+/// in-flight tile downloads to a configured bytes/second rate, so public OSM
+/// tile servers aren't hammered just because many tiles are fetched in
+/// parallel.
+///
+/// The bucket starts full, allowing a one-second burst before throttling
+/// kicks in.
+#[derive(Debug)]
+pub struct Throttle(TokenBucket);
+
+impl Throttle {
+    /// Creates a throttle capping throughput at `bytes_per_sec`.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self(TokenBucket::new(bytes_per_sec as f64))
+    }
+
+    /// Waits until `amount` bytes' worth of budget is available, then
+    /// consumes it.
+    pub async fn acquire(&self, amount: u64) {
+        self.0.acquire(amount as f64).await
+    }
+}
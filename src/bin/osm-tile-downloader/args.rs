@@ -2,10 +2,13 @@ use clap::{
     app_from_crate, crate_authors, crate_description, crate_name, crate_version,
     AppSettings, Arg, ArgMatches,
 };
-use std::{path::PathBuf, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use crate::validators::*;
-use osm_tile_downloader::{BoundingBox, Config, Fixture, UrlFormat};
+use osm_tile_downloader::{
+    is_local_output, parse_store, BoundingBox, Config, Fixture, OutputFormat, RateLimiter, Stitcher, Throttle,
+    TileStore, UrlFormat,
+};
 
 const URL_ARG: &str = "url";
 const ZOOM_ARG: &str = "zoom";
@@ -22,6 +25,15 @@ const DRY_RUN_ARG: &str = "dry_run";
 const REQUEST_RETRIES_ARG: &str = "num_retries";
 const PARALLEL_FETCHES_ARG: &str = "num_parallel";
 const FETCH_EXISTING_ARG: &str = "should_fetch_existing";
+const REFRESH_ARG: &str = "refresh";
+const MAX_BANDWIDTH_ARG: &str = "max_bandwidth";
+const MAX_REQUESTS_PER_SECOND_ARG: &str = "max_requests_per_second";
+const RESUME_ARG: &str = "resume";
+const RESTART_ARG: &str = "restart";
+const PROXY_ARG: &str = "proxy";
+const HEADER_ARG: &str = "header";
+const USER_AGENT_ARG: &str = "user_agent";
+const STITCH_ARG: &str = "stitch";
 
 pub struct Args {
     pub bounding_box: BoundingBox,
@@ -30,24 +42,73 @@ pub struct Args {
     pub timeout: Duration,
     pub min_zoom: u8,
     pub max_zoom: u8,
-    pub output_dir: PathBuf,
+    pub store: Arc<dyn TileStore>,
+    pub output_format: OutputFormat,
+    /// The raw `--output` path, used to locate the validator index and job
+    /// manifest. Only meaningful for local output.
+    pub output_path: PathBuf,
     pub url: String,
     pub fetch_existing: bool,
+    pub refresh: bool,
+    pub max_bandwidth: Option<u64>,
+    /// Caps how many tile requests are issued per second, independent of
+    /// `parallel_fetches`.
+    pub max_requests_per_second: Option<f64>,
+    /// Resume from the job manifest even if its stored parameters don't
+    /// match this run.
+    pub resume: bool,
+    /// Discard any existing job manifest and start over.
+    pub restart: bool,
+    pub proxy: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub user_agent: Option<String>,
+    /// Zoom levels to stitch into a composite PNG each, if any.
+    pub stitch: Option<Vec<u8>>,
     pub dry_run: bool,
 }
 
 impl std::convert::From<Args> for Config {
     fn from(args: Args) -> Self {
+        // `Stitcher` writes its composite PNG by joining a filename onto
+        // `--output`, which only addresses a writable location for a plain
+        // local directory; an S3 URL or a `.pmtiles` archive path has
+        // nowhere to put it
+        let local_output = is_local_output(&args.output_path.to_string_lossy());
+        let stitch = match (args.stitch, local_output) {
+            (Some(zoom_levels), true) => Some(Arc::new(Stitcher::new(
+                args.bounding_box,
+                args.output_path,
+                &zoom_levels,
+            ))),
+            (Some(_), false) => {
+                eprintln!("--stitch is only supported for local filesystem output, ignoring");
+                None
+            }
+            (None, _) => None,
+        };
+
         Self {
             bounding_box: args.bounding_box,
             fetch_existing: args.fetch_existing,
             fetch_rate: args.parallel_fetches,
-            output_folder: args.output_dir,
+            store: args.store,
+            output_format: args.output_format,
+            revalidation: None,
+            max_bandwidth: args.max_bandwidth.map(|rate| Arc::new(Throttle::new(rate))),
+            requests_per_second: args
+                .max_requests_per_second
+                .map(|rate| Arc::new(RateLimiter::new(rate))),
             request_retries_amount: args.retries,
-            url: UrlFormat::from_str(args.url),
+            manifest: None,
+            proxy: args.proxy,
+            user_agent: args.user_agent,
+            headers: args.headers,
+            url: UrlFormat::from_string(args.url).expect("invalid --url template"),
             timeout: args.timeout,
             min_zoom: args.min_zoom,
             max_zoom: args.max_zoom,
+            stitch,
+            on_tile_event: None,
         }
     }
 }
@@ -81,17 +142,18 @@ impl Args {
             ),
         };
 
-        let output_dir = {
-            let mut buf = PathBuf::new();
-            buf.push(matches.value_of(OUTPUT_DIR_ARG).unwrap());
-            buf
-        };
+        let output_arg = matches.value_of(OUTPUT_DIR_ARG).unwrap();
+        let (store, output_format) = parse_store(output_arg, bounding_box, min_zoom, max_zoom)
+            .expect("failed parsing --output");
+        let store: Arc<dyn TileStore> = store.into();
 
         Self {
             min_zoom,
             max_zoom,
             bounding_box,
-            output_dir,
+            store,
+            output_format,
+            output_path: PathBuf::from(output_arg),
             parallel_fetches: matches
                 .value_of(PARALLEL_FETCHES_ARG)
                 .unwrap()
@@ -107,6 +169,34 @@ impl Args {
             ),
             url: matches.value_of(URL_ARG).unwrap().to_owned(),
             fetch_existing: matches.is_present(FETCH_EXISTING_ARG),
+            refresh: matches.is_present(REFRESH_ARG),
+            max_bandwidth: matches
+                .value_of(MAX_BANDWIDTH_ARG)
+                .map(|v| v.parse::<bytesize::ByteSize>().unwrap().as_u64()),
+            max_requests_per_second: matches
+                .value_of(MAX_REQUESTS_PER_SECOND_ARG)
+                .map(|v| v.parse().unwrap()),
+            resume: matches.is_present(RESUME_ARG),
+            restart: matches.is_present(RESTART_ARG),
+            proxy: matches.value_of(PROXY_ARG).map(str::to_owned),
+            user_agent: matches.value_of(USER_AGENT_ARG).map(str::to_owned),
+            headers: matches
+                .values_of(HEADER_ARG)
+                .unwrap_or_default()
+                .map(|h| {
+                    let mut parts = h.splitn(2, ':');
+                    let name = parts.next().unwrap().trim().to_owned();
+                    let value = parts.next().unwrap().trim().to_owned();
+                    (name, value)
+                })
+                .collect(),
+            stitch: matches.value_of(STITCH_ARG).map(|v| {
+                if v.eq_ignore_ascii_case("max") {
+                    vec![max_zoom]
+                } else {
+                    v.split(',').map(|z| z.trim().parse().unwrap()).collect()
+                }
+            }),
             dry_run: matches.is_present(DRY_RUN_ARG),
         }
     }
@@ -216,7 +306,7 @@ fn get_matches() -> ArgMatches<'static> {
         )
         .arg(
             Arg::with_name(OUTPUT_DIR_ARG)
-                .help("The folder to output the tiles to. May contain format specifiers (and subfolders) to specify how the files will be laid out on disk.")
+                .help("Where to output the tiles. A local folder, an s3://bucket/key/{z}/{x}/{y}.png URL to upload directly to an S3-compatible bucket, or a path ending in .pmtiles to pack everything into a single PMTiles v3 archive.")
                 .default_value("output")
                 .takes_value(true)
                 .short("o")
@@ -224,7 +314,7 @@ fn get_matches() -> ArgMatches<'static> {
         )
         .arg(
             Arg::with_name(URL_ARG)
-                .help("The URL with format specifiers `{x}`, `{y}`, `{z}` to fetch the tiles from. Also supports the format specifier `{s}` which is replaced with `a`, `b` or `c` randomly to spread the load between different servers.")
+                .help("The URL with format specifiers `{x}`, `{z}` and one of `{y}` (XYZ), `{-y}` (TMS) or `{q}` (Bing quadkey) to address the tile row. Also supports the format specifier `{s}` which is replaced with `a`, `b` or `c` in rotation to spread the load between different servers.")
                 .required(true)
                 .takes_value(true)
                 .short("u")
@@ -237,6 +327,71 @@ fn get_matches() -> ArgMatches<'static> {
                 .takes_value(false)
                 .long("fetch-existing")
         )
+        .arg(
+            Arg::with_name(REFRESH_ARG)
+                .help("Conditionally revalidate previously fetched tiles via ETag/Last-Modified instead of re-downloading or skipping them outright")
+                .required(false)
+                .takes_value(false)
+                .conflicts_with(FETCH_EXISTING_ARG)
+                .long("refresh")
+        )
+        .arg(
+            Arg::with_name(MAX_BANDWIDTH_ARG)
+                .help("Caps aggregate download throughput across all parallel fetches, e.g. \"2MiB\" or \"500KB\"")
+                .validator(is_byte_size)
+                .takes_value(true)
+                .long("max-bandwidth")
+        )
+        .arg(
+            Arg::with_name(MAX_REQUESTS_PER_SECOND_ARG)
+                .help("Caps how many tile requests are issued per second in aggregate, independent of --rate, e.g. \"2\" or \"0.5\"")
+                .validator(is_positive_f64)
+                .takes_value(true)
+                .long("max-requests-per-second")
+        )
+        .arg(
+            Arg::with_name(PROXY_ARG)
+                .help("HTTP or SOCKS5 proxy URL to tunnel tile requests through, e.g. \"socks5://127.0.0.1:9050\". Defaults to respecting the HTTP_PROXY/HTTPS_PROXY/NO_PROXY environment variables.")
+                .takes_value(true)
+                .long("proxy")
+        )
+        .arg(
+            Arg::with_name(HEADER_ARG)
+                .help("Additional header sent with every tile request, e.g. \"Authorization: Bearer xyz\". May be repeated.")
+                .validator(is_header)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .long("header")
+        )
+        .arg(
+            Arg::with_name(USER_AGENT_ARG)
+                .help("Overrides the default User-Agent sent with tile requests")
+                .takes_value(true)
+                .long("user-agent")
+        )
+        .arg(
+            Arg::with_name(STITCH_ARG)
+                .help("Assemble the given zoom levels' tiles into one composite PNG each covering the bounding box, e.g. \"10,12\". Pass \"max\" to stitch only --max-zoom.")
+                .validator(is_stitch)
+                .takes_value(true)
+                .long("stitch")
+        )
+        .arg(
+            Arg::with_name(RESUME_ARG)
+                .help("Resume from the job manifest in --output even if the bounding box, zoom range or URL changed since the last run")
+                .required(false)
+                .takes_value(false)
+                .conflicts_with(RESTART_ARG)
+                .long("resume")
+        )
+        .arg(
+            Arg::with_name(RESTART_ARG)
+                .help("Discard any existing job manifest in --output and start over")
+                .required(false)
+                .takes_value(false)
+                .long("restart")
+        )
         .arg(
             Arg::with_name(DRY_RUN_ARG)
                 .help("Don't actually fetch anything, just determine how many tiles would be fetched.")
@@ -3,13 +3,31 @@ mod validators;
 
 use anyhow::Result;
 use args::Args;
-use osm_tile_downloader::{fetch, Config};
+use osm_tile_downloader::{fetch, is_local_output, Config, JobManifest, JobParams, ValidatorIndex};
+use std::sync::Arc;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
     let dry_run = args.dry_run;
-    let config: Config = args.into();
+    let refresh = args.refresh;
+    let resume = args.resume;
+    let restart = args.restart;
+    let output_path = args.output_path.clone();
+    let raw_url = args.url.clone();
+    // the manifest/validator index are JSON sidecar files joined onto
+    // `--output`, which only addresses a readable location for a plain local
+    // directory; an S3 URL or a `.pmtiles` archive path has nowhere to put them
+    let local_output = is_local_output(&output_path.to_string_lossy());
+    let mut config: Config = args.into();
+
+    if refresh {
+        if local_output {
+            config.revalidation = Some(Arc::new(ValidatorIndex::load(&output_path).await?));
+        } else {
+            eprintln!("--refresh is only supported for local filesystem output, ignoring");
+        }
+    }
 
     if dry_run {
         let tile_count = config
@@ -25,6 +43,21 @@ async fn main() -> Result<()> {
 
         Ok(())
     } else {
+        if local_output {
+            let params = JobParams {
+                bounding_box: config.bounding_box,
+                min_zoom: config.min_zoom,
+                max_zoom: config.max_zoom,
+                url: raw_url,
+            };
+            let all_tiles = config.tiles().collect();
+            config.manifest = Some(Arc::new(
+                JobManifest::load(&output_path, params, all_tiles, restart, resume).await?,
+            ));
+        } else if restart || resume {
+            eprintln!("--resume/--restart are only supported for local filesystem output, ignoring");
+        }
+
         fetch(config).await
     }
 }
@@ -31,3 +31,39 @@ pub fn is_bb_fixture(v: String) -> Result<(), String> {
         .map(|_| ())
         .map_err(|_| "invalid fixture".to_owned())
 }
+
+pub fn is_byte_size(v: String) -> Result<(), String> {
+    v.parse::<bytesize::ByteSize>().map(|_| ())
+}
+
+pub fn is_positive_f64(v: String) -> Result<(), String> {
+    let val = v.parse::<f64>().map_err(|_| "must be numeric".to_owned())?;
+
+    if val <= 0.0 {
+        return Err("must be > 0".to_owned());
+    }
+
+    Ok(())
+}
+
+pub fn is_header(v: String) -> Result<(), String> {
+    let mut parts = v.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(_)) if !name.trim().is_empty() => Ok(()),
+        _ => Err("must be of the form 'Name: Value'".to_owned()),
+    }
+}
+
+pub fn is_stitch(v: String) -> Result<(), String> {
+    if v.eq_ignore_ascii_case("max") {
+        return Ok(());
+    }
+
+    for zoom in v.split(',') {
+        zoom.trim()
+            .parse::<u8>()
+            .map_err(|_| "must be \"max\" or a comma-separated list of zoom levels".to_owned())?;
+    }
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::f64::consts::PI;
 use std::fmt::Debug;
 
@@ -11,7 +12,7 @@ use crate::tile::Tile;
 /// # use osm_tile_downloader::BoundingBox;
 /// let aachen_germany = BoundingBox::new_deg(50.811, 6.1649, 50.7492, 6.031);
 /// ```
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BoundingBox {
     pub north: f64,
     pub west: f64,
@@ -58,6 +59,20 @@ impl BoundingBox {
         }
     }
 
+    /// The north-west and south-east corner tiles covering this bounding box
+    /// at the given zoom level.
+    ///
+    /// # Panics
+    /// Panics if `zoom` is invalid.
+    pub fn tile_bounds(&self, zoom: u8) -> (Tile, Tile) {
+        assert!(zoom >= 1);
+
+        let nw = Tile::from_coords_and_zoom(self.north, self.west, zoom);
+        let se = Tile::from_coords_and_zoom(self.south, self.east, zoom);
+
+        (nw, se)
+    }
+
     /// Creates an iterator iterating over all tiles in the bounding box.
     ///
     /// # Panics
@@ -71,11 +86,10 @@ impl BoundingBox {
         assert!(max_zoom >= 1);
         assert!(min_zoom <= max_zoom);
 
-        let (n, e, s, w) = (self.north, self.east, self.south, self.west);
+        let this = *self;
 
         (min_zoom..=max_zoom).flat_map(move |zoom| {
-            let nw = Tile::from_coords_and_zoom(n, w, zoom);
-            let se = Tile::from_coords_and_zoom(s, e, zoom);
+            let (nw, se) = this.tile_bounds(zoom);
 
             ((nw.x)..=(se.x)).flat_map(move |x| {
                 ((nw.y)..=(se.y)).map(move |y| Tile::new(x, y, zoom))
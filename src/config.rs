@@ -1,11 +1,16 @@
-use std::{fmt::Debug, path::PathBuf, time::Duration};
+use std::{fmt, sync::Arc, time::Duration};
 
+use crate::bandwidth::Throttle;
 use crate::bounding_box::BoundingBox;
-use crate::tile::Tile;
+use crate::index::ValidatorIndex;
+use crate::manifest::JobManifest;
+use crate::rate_limit::RateLimiter;
+use crate::stitch::Stitcher;
+use crate::store::{OutputFormat, TileStore};
+use crate::tile::{Tile, TileEvent};
 use crate::url::UrlFormat;
 
 /// Tile fetching configuration.
-#[derive(Debug, PartialEq)]
 pub struct Config {
     /// Bounding box in top, right, bottom, left order.
     pub bounding_box: BoundingBox,
@@ -13,15 +18,55 @@ pub struct Config {
     /// Whether to skip tiles that are already downloaded.
     pub fetch_existing: bool,
 
-    /// Maximum number of parallel downloads.
+    /// Maximum number of parallel downloads. This only bounds concurrency,
+    /// not throughput; pair it with `requests_per_second` to actually cap
+    /// how fast requests are issued.
     pub fetch_rate: u8,
 
-    /// The folder to output the data to.
-    pub output_folder: PathBuf,
+    /// The store tiles are written to, e.g. the local filesystem or an S3 bucket.
+    pub store: Arc<dyn TileStore>,
+
+    /// The on-disk shape `store` writes tiles in.
+    pub output_format: OutputFormat,
+
+    /// When set, enables conditional revalidation: previously-seen `ETag` /
+    /// `Last-Modified` validators are sent with every request and unchanged
+    /// tiles are kept as-is instead of being re-downloaded wholesale.
+    pub revalidation: Option<Arc<ValidatorIndex>>,
+
+    /// When set, caps the aggregate download rate across all parallel
+    /// fetches to this many bytes per second.
+    pub max_bandwidth: Option<Arc<Throttle>>,
+
+    /// When set, caps the aggregate number of tile requests issued per
+    /// second across all parallel fetches, regardless of `fetch_rate`. This
+    /// is what actually keeps well-behaved bulk downloads within a tile
+    /// server's usage policy; `fetch_rate` alone only limits concurrency.
+    pub requests_per_second: Option<Arc<RateLimiter>>,
 
     /// How many times to retry a failed HTTP request.
     pub request_retries_amount: u8,
 
+    /// When set, enables resumable jobs: fetch progress is tracked in a
+    /// checkpoint manifest so an interrupted run can continue without
+    /// re-checking every output file.
+    pub manifest: Option<Arc<JobManifest>>,
+
+    /// HTTP or SOCKS5 proxy URL to tunnel requests through.
+    ///
+    /// When unset, the client falls back to the system's `HTTP_PROXY` /
+    /// `HTTPS_PROXY` / `NO_PROXY` environment variables, as `reqwest` does by
+    /// default.
+    pub proxy: Option<String>,
+
+    /// `User-Agent` sent with every tile request. Defaults to identifying
+    /// this crate and its version, which satisfies OSM's tile usage policy
+    /// unless overridden.
+    pub user_agent: Option<String>,
+
+    /// Additional headers sent with every tile request.
+    pub headers: Vec<(String, String)>,
+
     /// The URL to download individual tiles from including the replacement
     /// specifiers `{x}`, `{y}` and `{z}`.
     pub url: UrlFormat,
@@ -36,11 +81,47 @@ pub struct Config {
 
     /// The maximum zoom level to download to.
     pub max_zoom: u8,
+
+    /// When set, assembles each listed zoom level's tiles into one
+    /// composite PNG covering the bounding box, in addition to writing them
+    /// through `store` as usual.
+    pub stitch: Option<Arc<Stitcher>>,
+
+    /// Called after every tile's fetch attempt (success, skip or final
+    /// failure). Lets library users drive their own progress UI instead of
+    /// the built-in one.
+    pub on_tile_event: Option<Arc<dyn Fn(TileEvent) + Send + Sync>>,
 }
 
 impl Config {
     /// Creates an iterator iterating over all tiles in the contained bounding box.
-    pub fn tiles(&self) -> impl Iterator<Item = Tile> + Debug {
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + fmt::Debug {
         self.bounding_box.tiles(self.min_zoom, self.max_zoom)
     }
 }
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("bounding_box", &self.bounding_box)
+            .field("fetch_existing", &self.fetch_existing)
+            .field("fetch_rate", &self.fetch_rate)
+            .field("store", &self.store)
+            .field("output_format", &self.output_format)
+            .field("revalidation", &self.revalidation)
+            .field("max_bandwidth", &self.max_bandwidth)
+            .field("requests_per_second", &self.requests_per_second)
+            .field("request_retries_amount", &self.request_retries_amount)
+            .field("manifest", &self.manifest)
+            .field("proxy", &self.proxy)
+            .field("user_agent", &self.user_agent)
+            .field("headers", &self.headers)
+            .field("url", &self.url)
+            .field("timeout", &self.timeout)
+            .field("min_zoom", &self.min_zoom)
+            .field("max_zoom", &self.max_zoom)
+            .field("stitch", &self.stitch)
+            .field("on_tile_event", &self.on_tile_event.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
@@ -2,58 +2,99 @@ use anyhow::{Context, Result};
 use clap::crate_version;
 use futures::{prelude::*, stream};
 use indicatif::{ProgressBar, ProgressStyle};
-use std::time::Duration;
-use tokio::fs;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use crate::config::Config;
+use crate::host_limit::HostLimiter;
+use crate::tile::{FetchOptions, Tile, TileEvent};
 
-pub(crate) const BACKOFF_DELAY: Duration = Duration::from_secs(10);
 const ZERO_DURATION: Duration = Duration::from_secs(0);
+const MAX_CONCURRENT_REQUESTS_PER_HOST: usize = 4;
+
+/// Tracks a current (not lifetime-average) bytes/sec rate for the progress
+/// bar's `{msg}`, the way network-monitor tools display per-interval
+/// throughput rather than an average that never reacts to slowdowns once a
+/// job has run for a while.
+struct Throughput {
+    last_sample: Mutex<(Instant, u64)>,
+}
+
+impl Throughput {
+    fn new() -> Self {
+        Self {
+            last_sample: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    /// Returns the bytes/sec rate observed since the previous call, given
+    /// the job's new running total of bytes transferred.
+    fn sample(&self, total_bytes: u64) -> f64 {
+        let mut last = self.last_sample.lock().unwrap();
+        let elapsed = last.0.elapsed().as_secs_f64().max(0.001);
+        let rate = total_bytes.saturating_sub(last.1) as f64 / elapsed;
+
+        *last = (Instant::now(), total_bytes);
+        rate
+    }
+}
 
 /// Asynchronously fetch the open street map tiles specified in `cfg` and save them
-/// to the file system.
+/// through the configured [`TileStore`](crate::TileStore).
 ///
-/// Creates the required directories recursively and overwrites any existing files
-/// at the destination.
+/// Overwrites any existing tiles at the destination, unless `fetch_existing` is
+/// `false` and the store already reports them as present. With `revalidation`
+/// set, a tile that has stored validators is requested conditionally instead,
+/// whether or not it is present: it is kept as-is on `304 Not Modified` and
+/// overwritten otherwise.
 ///
 /// # Example
 /// ```rust
-/// use osm_tile_downloader::{fetch, BoundingBox, Config};
-/// # use std::path::Path;
+/// use osm_tile_downloader::{fetch, BoundingBox, Config, LocalStore, OutputFormat};
+/// use std::{sync::Arc, time::Duration};
 ///
 /// # #[tokio::main]
 /// # async fn main() {
 /// let config = Config {
 ///     bounding_box: BoundingBox::new_deg(50.811, 6.1649, 50.7492, 6.031),
 ///     fetch_rate: 10,
-///     output_folder: Path::new("./tiles"),
+///     store: Arc::new(LocalStore::new("./tiles".into())),
+///     output_format: OutputFormat::Directory,
+///     revalidation: None,
 ///     request_retries_amount: 3,
-///     url: "https://{s}.tile.openstreetmap.de/{z}/{x}/{y}.png",
-///     timeout_secs: 30,
+///     manifest: None,
+///     proxy: None,
+///     user_agent: None,
+///     headers: Vec::new(),
+///     url: osm_tile_downloader::UrlFormat::from_string(
+///         "https://{s}.tile.openstreetmap.de/{z}/{x}/{y}.png".into(),
+///     )
+///     .expect("invalid url template"),
+///     timeout: Duration::from_secs(30),
+///     min_zoom: 1,
 ///     max_zoom: 10,
+///     fetch_existing: false,
+///     max_bandwidth: None,
+///     requests_per_second: None,
+///     stitch: None,
+///     on_tile_event: None,
 /// };
 ///
 /// fetch(config).await.expect("failed fetching tiles");
 /// # }
 /// ```
-///
-/// # Panics
-/// Panics if the specified output folder exists and is not a folder but a file.
 pub async fn fetch(cfg: Config) -> Result<()> {
-    let output_folder = cfg.output_folder.as_path();
+    let tiles: Vec<Tile> = match &cfg.manifest {
+        Some(manifest) => manifest.tiles_to_fetch(),
+        None => cfg.tiles().collect(),
+    };
 
-    assert!(
-        !output_folder.exists() || output_folder.is_dir(),
-        "output must be a directory",
-    );
-
-    if !output_folder.exists() {
-        fs::create_dir_all(output_folder)
-            .await
-            .context("failed to create root output directory")?;
-    }
-
-    let pb = ProgressBar::new(cfg.tiles().count() as u64);
+    let pb = ProgressBar::new(tiles.len() as u64);
     pb.set_style(
         ProgressStyle::default_bar()
             .template("[{elapsed_precise}] {bar:60.cyan/blue} {pos:>7}/{len:7} ETA: {eta} {msg}")
@@ -65,55 +106,149 @@ pub async fn fetch(cfg: Config) -> Result<()> {
         builder = builder.timeout(cfg.timeout);
     }
 
+    if let Some(proxy) = &cfg.proxy {
+        builder = builder.proxy(
+            reqwest::Proxy::all(proxy)
+                .with_context(|| format!("invalid --proxy URL {}", proxy))?,
+        );
+    }
+
     let mut headers = reqwest::header::HeaderMap::new();
+    let user_agent = cfg
+        .user_agent
+        .clone()
+        .unwrap_or_else(|| format!("osm-tile-downloader_rs_{}", crate_version!()));
     headers.append(
         reqwest::header::USER_AGENT,
-        format!("osm-tile-downloader_rs_{}", crate_version!())
+        user_agent
             .parse()
-            .unwrap(),
+            .with_context(|| format!("invalid --user-agent value {}", user_agent))?,
     );
 
+    for (name, value) in &cfg.headers {
+        let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("invalid --header name {}", name))?;
+        let value = value
+            .parse()
+            .with_context(|| format!("invalid --header value for {}", name))?;
+        headers.append(name, value);
+    }
+
     let client = builder
         .default_headers(headers)
         .build()
         .with_context(|| "failed creating HTTP client")?;
 
-    let num_retries = cfg.request_retries_amount;
-    let fetch_existing = cfg.fetch_existing;
     let url_fmt = &cfg.url;
-
-    let progress_bar = pb.wrap_iter(cfg.tiles());
+    let host_limiter = HostLimiter::new(MAX_CONCURRENT_REQUESTS_PER_HOST);
+    let opts = FetchOptions {
+        store: cfg.store.as_ref(),
+        fetch_existing: cfg.fetch_existing,
+        revalidation: cfg.revalidation.as_deref(),
+        bandwidth: cfg.max_bandwidth.as_deref(),
+        host_limiter: Some(&host_limiter),
+        stitcher: cfg.stitch.as_deref(),
+        max_attempts: cfg.request_retries_amount,
+    };
+
+    let bytes_done = AtomicU64::new(0);
+    let throughput = Throughput::new();
+    let pb_for_msg = pb.clone();
+
+    let progress_bar = pb.wrap_iter(tiles.into_iter());
     let s = stream::iter(progress_bar);
     s.for_each_concurrent(cfg.fetch_rate as usize, |tile| {
         let http_client = client.clone();
+        let pb = pb_for_msg.clone();
+        let bytes_done = &bytes_done;
+        let throughput = &throughput;
+        let opts = &opts;
+        let manifest = cfg.manifest.as_deref();
+        let on_tile_event = cfg.on_tile_event.as_deref();
+        let requests_per_second = cfg.requests_per_second.as_deref();
 
         async move {
-            let mut res = Ok(());
-
-            for _ in 0..num_retries {
-                res = tile
-                    .fetch_from(&http_client, url_fmt, output_folder, fetch_existing)
-                    .await;
+            if let Some(limiter) = requests_per_second {
+                limiter.acquire().await;
+            }
 
-                if res.is_ok() {
-                    return;
+            match tile.fetch_from(&http_client, url_fmt, opts).await {
+                Ok(n) => {
+                    let total = bytes_done.fetch_add(n, Ordering::Relaxed) + n;
+                    pb.set_message(&format!(
+                        "{}/s",
+                        pretty_bytes::converter::convert(throughput.sample(total))
+                    ));
+
+                    if let Some(cb) = on_tile_event {
+                        cb(TileEvent::Success { tile, bytes: n });
+                    }
+
+                    if let Some(manifest) = manifest {
+                        if let Err(e) = manifest.mark_done(&tile).await {
+                            eprintln!("Failed updating job manifest: {:?}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Failed fetching tile {}x{}x{}: {:?}",
+                        tile.z, tile.x, tile.y, e,
+                    );
+
+                    if let Some(cb) = on_tile_event {
+                        cb(TileEvent::Failure {
+                            tile,
+                            message: format!("{:?}", e),
+                        });
+                    }
+
+                    if let Some(manifest) = manifest {
+                        manifest.mark_failed(&tile);
+                    }
                 }
-
-                tokio::time::delay_for(BACKOFF_DELAY).await;
             }
-
-            eprintln!(
-                "Failed fetching tile {}x{}x{}: {:?}",
-                tile.z,
-                tile.x,
-                tile.y,
-                res.unwrap_err(),
-            );
         }
     })
     .await;
 
     pb.finish_and_clear();
 
+    if let Some(index) = &cfg.revalidation {
+        index.save().await.context("failed saving validator index")?;
+    }
+
+    if let Some(manifest) = &cfg.manifest {
+        manifest.save().await.context("failed saving job manifest")?;
+    }
+
+    cfg.store.finalize().await.context("failed finalizing tile store")?;
+
+    if let Some(stitcher) = &cfg.stitch {
+        stitcher.finish().await.context("failed writing stitched images")?;
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    #[test]
+    fn throughput_reports_rate_since_last_sample_not_since_start() {
+        let throughput = Throughput::new();
+
+        sleep(Duration::from_millis(50));
+        let first = throughput.sample(1_000_000);
+
+        // a long idle gap followed by a tiny transfer should read as a slow
+        // rate, not get dragged up by the fast first sample the way a
+        // lifetime average would
+        sleep(Duration::from_millis(200));
+        let second = throughput.sample(1_000_100);
+
+        assert!(first > second);
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::Semaphore;
+
+/// Caps the number of concurrent in-flight requests per resolved host.
+///
+/// OSM-style templates rotate through `{s}` subdomains (`a`/`b`/`c`) that all
+/// resolve to the same origin, so keying purely by the literal hostname
+/// would hand out one semaphore per subdomain and let concurrency triple
+/// despite `--rate`. [`semaphore_for`](Self::semaphore_for) resolves the
+/// host via DNS first and keys on the resolved address set instead, so
+/// subdomains that alias the same origin share one semaphore. Each distinct
+/// resolved host gets its own semaphore, created lazily on first use.
+#[derive(Debug)]
+pub struct HostLimiter {
+    max_per_host: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    pub fn new(max_per_host: usize) -> Self {
+        Self {
+            max_per_host,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the semaphore for the given host, creating it on first use.
+    ///
+    /// Resolves `host` via DNS and keys on the sorted, deduplicated address
+    /// set so that e.g. `a.tile.osm.org` and `b.tile.osm.org` share one
+    /// semaphore if they resolve to the same IPs. Falls back to keying on
+    /// the literal hostname if resolution fails, so a DNS hiccup degrades to
+    /// the old (less precise) behavior rather than failing the request.
+    pub async fn semaphore_for(&self, host: &str) -> Arc<Semaphore> {
+        let key = resolve_key(host).await.unwrap_or_else(|| host.to_owned());
+
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts
+            .entry(key)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_host)))
+            .clone()
+    }
+}
+
+/// Resolves `host` (optionally `host:port`) to its set of IP addresses and
+/// joins them into a stable, order-independent string to key the host map
+/// with. The port, if any, is stripped before resolution since
+/// [`tokio::net::lookup_host`]'s `(host, port)` form expects a bare
+/// hostname, and two `{s}` subdomains on different ports still alias the
+/// same origin for rate-limiting purposes.
+async fn resolve_key(host: &str) -> Option<String> {
+    let hostname = strip_port(host);
+
+    let mut addrs: Vec<_> = tokio::net::lookup_host((hostname, 0))
+        .await
+        .ok()?
+        .map(|addr| addr.ip().to_string())
+        .collect();
+
+    if addrs.is_empty() {
+        return None;
+    }
+
+    addrs.sort_unstable();
+    addrs.dedup();
+
+    Some(addrs.join(","))
+}
+
+/// Strips a trailing `:port` suffix from a `host[:port]` string, if present.
+///
+/// Only strips when there's exactly one `:` in the whole string, so a bare
+/// (unbracketed) IPv6 literal — which contains several — is left alone
+/// rather than mangled.
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(idx)
+            if host.matches(':').count() == 1
+                && !host[idx + 1..].is_empty()
+                && host[idx + 1..].chars().all(|c| c.is_ascii_digit()) =>
+        {
+            &host[..idx]
+        }
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_port_removes_trailing_port() {
+        assert_eq!(strip_port("tile.osm.org:8080"), "tile.osm.org");
+        assert_eq!(strip_port("tile.osm.org"), "tile.osm.org");
+        assert_eq!(strip_port("::1"), "::1");
+    }
+}
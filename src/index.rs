@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::fs;
+
+use crate::tile::Tile;
+
+const INDEX_FILE_NAME: &str = ".osm-tile-downloader-index.json";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// A JSON sidecar file recording the `ETag` / `Last-Modified` response
+/// headers seen for each tile, keyed by `z/x/y`.
+///
+/// This lets a later run send `If-None-Match` / `If-Modified-Since` instead
+/// of either blindly re-downloading (`--fetch-existing`) or trusting a local
+/// file that may have gone stale upstream.
+#[derive(Debug)]
+pub struct ValidatorIndex {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, Validators>>,
+}
+
+impl ValidatorIndex {
+    /// Loads the index from `{root}/.osm-tile-downloader-index.json`, or
+    /// starts an empty one if no such file exists yet.
+    pub async fn load(root: &Path) -> Result<Self> {
+        fs::create_dir_all(root)
+            .await
+            .with_context(|| format!("failed creating output directory {}", root.display()))?;
+
+        let path = root.join(INDEX_FILE_NAME);
+
+        let entries = match fs::read(&path).await {
+            Ok(raw) => serde_json::from_slice(&raw).with_context(|| {
+                format!("failed parsing validator index at {}", path.display())
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("failed reading validator index at {}", path.display())
+                })
+            }
+        };
+
+        Ok(Self {
+            path,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    fn key(tile: &Tile) -> String {
+        format!("{}/{}/{}", tile.z, tile.x, tile.y)
+    }
+
+    /// Returns the stored `(ETag, Last-Modified)` validators for a tile, if any.
+    pub fn get(&self, tile: &Tile) -> (Option<String>, Option<String>) {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&Self::key(tile))
+            .map(|v| (v.etag.clone(), v.last_modified.clone()))
+            .unwrap_or_default()
+    }
+
+    /// Records the validators observed on a tile's latest response.
+    pub fn set(&self, tile: &Tile, etag: Option<String>, last_modified: Option<String>) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(
+            Self::key(tile),
+            Validators {
+                etag,
+                last_modified,
+            },
+        );
+    }
+
+    /// Persists the index back to disk, overwriting any previous contents.
+    pub async fn save(&self) -> Result<()> {
+        let serialized = {
+            let entries = self.entries.lock().unwrap();
+            serde_json::to_vec_pretty(&*entries).context("failed serializing validator index")?
+        };
+
+        fs::write(&self.path, serialized).await.with_context(|| {
+            format!("failed writing validator index to {}", self.path.display())
+        })
+    }
+}
@@ -27,37 +27,64 @@
 //!
 //! # Library Example
 //! ```rust
-//! use osm_tile_downloader::{fetch, BoundingBox, Config, UrlFormat};
-//! use std::time::Duration;
+//! use osm_tile_downloader::{fetch, BoundingBox, Config, LocalStore, OutputFormat, UrlFormat};
+//! use std::{sync::Arc, time::Duration};
 //!
 //! # #[tokio::main]
 //! # async fn main() {
 //! let config = Config {
 //!     bounding_box: BoundingBox::new_deg(50.811, 6.1649, 50.7492, 6.031),
 //!     fetch_rate: 10,
-//!     output_folder: "./tiles".into(),
+//!     store: Arc::new(LocalStore::new("./tiles".into())),
+//!     output_format: OutputFormat::Directory,
+//!     revalidation: None,
 //!     request_retries_amount: 3,
-//!     url: UrlFormat::from_string("https://{s}.tile.openstreetmap.de/{z}/{x}/{y}.png".into()),
+//!     manifest: None,
+//!     proxy: None,
+//!     user_agent: None,
+//!     headers: Vec::new(),
+//!     url: UrlFormat::from_string("https://{s}.tile.openstreetmap.de/{z}/{x}/{y}.png".into())
+//!         .expect("invalid url template"),
 //!     timeout: Duration::from_secs(30),
 //!     min_zoom: 1,
 //!     max_zoom: 2,
 //!     fetch_existing: false,
+//!     max_bandwidth: None,
+//!     requests_per_second: None,
+//!     stitch: None,
+//!     on_tile_event: None,
 //! };
 //!
 //! fetch(config).await.expect("failed fetching tiles");
 //! # }
 //! ```
 
+mod backoff;
+mod bandwidth;
 mod bounding_box;
 mod config;
 mod fetch;
+mod host_limit;
+mod index;
+mod manifest;
+mod pmtiles;
+mod rate_limit;
+mod stitch;
+mod store;
 mod tile;
+mod token_bucket;
 mod url;
 
+pub use bandwidth::Throttle;
 pub use bounding_box::{BoundingBox, Fixture};
 pub use config::Config;
 pub use fetch::fetch;
-pub use tile::Tile;
+pub use index::ValidatorIndex;
+pub use manifest::{JobManifest, JobParams};
+pub use rate_limit::RateLimiter;
+pub use stitch::Stitcher;
+pub use store::{is_local_output, parse_store, LocalStore, OutputFormat, PmTilesStore, S3Store, TileStore};
+pub use tile::{FetchOptions, Tile, TileEvent};
 pub use url::UrlFormat;
 
 #[cfg(test)]
@@ -0,0 +1,227 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::fs;
+
+use crate::bounding_box::BoundingBox;
+use crate::tile::Tile;
+
+const MANIFEST_FILE_NAME: &str = ".osm-tile-downloader-manifest.json";
+
+/// Flush the manifest to disk after this many completed tiles, rather than
+/// fsyncing on every single one.
+const FLUSH_EVERY: usize = 50;
+
+/// The parts of a job that decide whether a previous run's manifest still
+/// applies to this one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobParams {
+    pub bounding_box: BoundingBox,
+    pub min_zoom: u8,
+    pub max_zoom: u8,
+    pub url: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ManifestData {
+    params: Option<JobParams>,
+    pending: HashSet<Tile>,
+    failed: HashSet<Tile>,
+}
+
+/// A JSON checkpoint file recording which tiles of a job are still
+/// outstanding, so an interrupted multi-zoom download can resume without
+/// re-`stat`ing every output file.
+#[derive(Debug)]
+pub struct JobManifest {
+    path: PathBuf,
+    data: Mutex<ManifestData>,
+    since_flush: Mutex<usize>,
+}
+
+impl JobManifest {
+    /// Loads the manifest at `{root}/.osm-tile-downloader-manifest.json`, or
+    /// starts a fresh one covering `all_tiles`.
+    ///
+    /// The existing manifest is resumed from if `force_resume` is set, or if
+    /// its stored [`JobParams`] match `params`; otherwise (including when
+    /// `restart` is set) it's discarded and every tile in `all_tiles` starts
+    /// out pending. Either way, the manifest is written to disk immediately
+    /// so a crash before the first tile completes still leaves a resumable
+    /// state file behind.
+    pub async fn load(
+        root: &Path,
+        params: JobParams,
+        all_tiles: Vec<Tile>,
+        restart: bool,
+        force_resume: bool,
+    ) -> Result<Self> {
+        fs::create_dir_all(root)
+            .await
+            .with_context(|| format!("failed creating output directory {}", root.display()))?;
+
+        let path = root.join(MANIFEST_FILE_NAME);
+
+        let existing = if restart {
+            None
+        } else {
+            match fs::read(&path).await {
+                Ok(raw) => Some(serde_json::from_slice::<ManifestData>(&raw).with_context(
+                    || format!("failed parsing job manifest at {}", path.display()),
+                )?),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("failed reading job manifest at {}", path.display()))
+                }
+            }
+        };
+
+        let data = resolve_data(existing, params, all_tiles, force_resume);
+
+        let manifest = Self {
+            path,
+            data: Mutex::new(data),
+            since_flush: Mutex::new(0),
+        };
+        manifest.save().await?;
+
+        Ok(manifest)
+    }
+
+    /// The tiles that still need to be fetched: those left pending, plus ones
+    /// previously recorded as failed.
+    pub fn tiles_to_fetch(&self) -> Vec<Tile> {
+        let data = self.data.lock().unwrap();
+        data.pending.iter().chain(data.failed.iter()).copied().collect()
+    }
+
+    /// Marks `tile` as successfully fetched, flushing to disk every
+    /// [`FLUSH_EVERY`] completions.
+    pub async fn mark_done(&self, tile: &Tile) -> Result<()> {
+        let should_flush = {
+            let mut data = self.data.lock().unwrap();
+            data.pending.remove(tile);
+            data.failed.remove(tile);
+
+            let mut since_flush = self.since_flush.lock().unwrap();
+            *since_flush += 1;
+            if *since_flush >= FLUSH_EVERY {
+                *since_flush = 0;
+                true
+            } else {
+                false
+            }
+        };
+
+        if should_flush {
+            self.save().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Marks `tile` as failed, so the next run retries it.
+    pub fn mark_failed(&self, tile: &Tile) {
+        let mut data = self.data.lock().unwrap();
+        data.pending.remove(tile);
+        data.failed.insert(*tile);
+    }
+
+    /// Persists the manifest to disk, overwriting any previous contents.
+    pub async fn save(&self) -> Result<()> {
+        let serialized = {
+            let data = self.data.lock().unwrap();
+            serde_json::to_vec_pretty(&*data).context("failed serializing job manifest")?
+        };
+
+        fs::write(&self.path, serialized)
+            .await
+            .with_context(|| format!("failed writing job manifest to {}", self.path.display()))
+    }
+}
+
+/// Decides whether a manifest read from disk still applies: resumed as-is if
+/// `force_resume` is set or its stored [`JobParams`] match `params`,
+/// otherwise discarded in favor of a fresh manifest covering `all_tiles`.
+fn resolve_data(
+    existing: Option<ManifestData>,
+    params: JobParams,
+    all_tiles: Vec<Tile>,
+    force_resume: bool,
+) -> ManifestData {
+    match existing {
+        Some(data) if force_resume || data.params.as_ref() == Some(&params) => data,
+        _ => ManifestData {
+            params: Some(params),
+            pending: all_tiles.into_iter().collect(),
+            failed: HashSet::new(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounding_box::BoundingBox;
+
+    fn params(max_zoom: u8) -> JobParams {
+        JobParams {
+            bounding_box: BoundingBox::new_deg(1.0, 1.0, 0.0, 0.0),
+            min_zoom: 1,
+            max_zoom,
+            url: "https://tile/{x}/{y}/{z}.png".to_owned(),
+        }
+    }
+
+    fn existing_with(params: JobParams) -> ManifestData {
+        ManifestData {
+            params: Some(params),
+            pending: HashSet::new(),
+            failed: [Tile::new(1, 2, 3)].iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn resumes_when_params_match() {
+        let existing = existing_with(params(5));
+
+        let data = resolve_data(Some(existing), params(5), vec![], false);
+
+        assert_eq!(data.failed.len(), 1);
+    }
+
+    #[test]
+    fn restarts_when_params_differ() {
+        let existing = existing_with(params(5));
+        let all_tiles = vec![Tile::new(0, 0, 1)];
+
+        let data = resolve_data(Some(existing), params(6), all_tiles, false);
+
+        assert!(data.failed.is_empty());
+        assert_eq!(data.pending.len(), 1);
+    }
+
+    #[test]
+    fn force_resume_ignores_mismatched_params() {
+        let existing = existing_with(params(5));
+
+        let data = resolve_data(Some(existing), params(6), vec![], true);
+
+        assert_eq!(data.failed.len(), 1);
+    }
+
+    #[test]
+    fn starts_fresh_when_nothing_on_disk() {
+        let all_tiles = vec![Tile::new(0, 0, 1), Tile::new(1, 0, 1)];
+
+        let data = resolve_data(None, params(5), all_tiles, false);
+
+        assert!(data.failed.is_empty());
+        assert_eq!(data.pending.len(), 2);
+    }
+}
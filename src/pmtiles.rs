@@ -0,0 +1,330 @@
+use anyhow::{Context, Result};
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::Write,
+};
+
+use crate::bounding_box::BoundingBox;
+use crate::tile::Tile;
+
+const MAGIC: &[u8; 7] = b"PMTiles";
+const VERSION: u8 = 3;
+const HEADER_LEN: usize = 127;
+
+// https://github.com/protomaps/PMTiles/blob/main/spec/v3/spec.md
+const COMPRESSION_NONE: u8 = 1;
+const COMPRESSION_GZIP: u8 = 2;
+const TILE_TYPE_PNG: u8 = 2;
+
+struct DirEntry {
+    tile_id: u64,
+    offset: u64,
+    length: u32,
+    run_length: u32,
+}
+
+/// Packs tiles into a single PMTiles v3 archive instead of a `{z}/{x}/{y}.png`
+/// directory tree, so the result is directly serveable over HTTP range
+/// requests without a tile server.
+///
+/// Tiles are addressed by a [Hilbert curve](tile_id) index and deduplicated
+/// by content hash, so identical tiles (common at low zoom, e.g. open ocean)
+/// only occupy the data section once. Only a root directory is produced;
+/// this tool doesn't generate archives large enough to need leaf
+/// directories, which the spec makes optional.
+#[derive(Debug)]
+pub struct PmTilesWriter {
+    bounding_box: BoundingBox,
+    min_zoom: u8,
+    max_zoom: u8,
+    data: Vec<u8>,
+    offset_by_hash: HashMap<u64, (u64, u32)>,
+    entries: Vec<DirEntry>,
+}
+
+impl PmTilesWriter {
+    pub fn new(bounding_box: BoundingBox, min_zoom: u8, max_zoom: u8) -> Self {
+        Self {
+            bounding_box,
+            min_zoom,
+            max_zoom,
+            data: Vec::new(),
+            offset_by_hash: HashMap::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Appends a tile's blob to the data section, or reuses an existing
+    /// entry if an identical blob was already written.
+    pub fn add_tile(&mut self, tile: &Tile, bytes: &[u8]) {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let data = &mut self.data;
+        let &mut (offset, length) = self.offset_by_hash.entry(hash).or_insert_with(|| {
+            let offset = data.len() as u64;
+            data.extend_from_slice(bytes);
+            (offset, bytes.len() as u32)
+        });
+
+        self.entries.push(DirEntry {
+            tile_id: tile_id(tile.z, tile.x, tile.y),
+            offset,
+            length,
+            run_length: 1,
+        });
+    }
+
+    /// Serializes every tile added so far into a complete `.pmtiles` archive.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut entries: Vec<&DirEntry> = self.entries.iter().collect();
+        entries.sort_unstable_by_key(|e| e.tile_id);
+
+        let merged = merge_runs(&entries);
+        let root_dir = gzip(&serialize_directory(&merged))?;
+        let json_metadata = gzip(b"{}")?;
+
+        let header_len = HEADER_LEN as u64;
+        let root_dir_offset = header_len;
+        let json_metadata_offset = root_dir_offset + root_dir.len() as u64;
+        let leaf_dirs_offset = json_metadata_offset + json_metadata.len() as u64;
+        let tile_data_offset = leaf_dirs_offset;
+
+        let header = self.encode_header(HeaderLayout {
+            root_dir_offset,
+            root_dir_length: root_dir.len() as u64,
+            json_metadata_offset,
+            json_metadata_length: json_metadata.len() as u64,
+            leaf_dirs_offset,
+            leaf_dirs_length: 0,
+            tile_data_offset,
+            tile_data_length: self.data.len() as u64,
+            addressed_tiles_count: self.entries.len() as u64,
+            tile_entries_count: merged.len() as u64,
+            tile_contents_count: self.offset_by_hash.len() as u64,
+        });
+
+        let mut archive = Vec::with_capacity(header.len() + root_dir.len() + json_metadata.len() + self.data.len());
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(&root_dir);
+        archive.extend_from_slice(&json_metadata);
+        archive.extend_from_slice(&self.data);
+
+        Ok(archive)
+    }
+
+    fn encode_header(&self, layout: HeaderLayout) -> [u8; HEADER_LEN] {
+        let mut header = [0u8; HEADER_LEN];
+
+        header[0..7].copy_from_slice(MAGIC);
+        header[7] = VERSION;
+        header[8..16].copy_from_slice(&layout.root_dir_offset.to_le_bytes());
+        header[16..24].copy_from_slice(&layout.root_dir_length.to_le_bytes());
+        header[24..32].copy_from_slice(&layout.json_metadata_offset.to_le_bytes());
+        header[32..40].copy_from_slice(&layout.json_metadata_length.to_le_bytes());
+        header[40..48].copy_from_slice(&layout.leaf_dirs_offset.to_le_bytes());
+        header[48..56].copy_from_slice(&layout.leaf_dirs_length.to_le_bytes());
+        header[56..64].copy_from_slice(&layout.tile_data_offset.to_le_bytes());
+        header[64..72].copy_from_slice(&layout.tile_data_length.to_le_bytes());
+        header[72..80].copy_from_slice(&layout.addressed_tiles_count.to_le_bytes());
+        header[80..88].copy_from_slice(&layout.tile_entries_count.to_le_bytes());
+        header[88..96].copy_from_slice(&layout.tile_contents_count.to_le_bytes());
+        header[96] = 0; // clustered: entries aren't guaranteed contiguous in `data`
+        header[97] = COMPRESSION_GZIP; // internal_compression (directories, metadata)
+        header[98] = COMPRESSION_NONE; // tile_compression: blobs are stored as fetched
+        header[99] = TILE_TYPE_PNG;
+        header[100] = self.min_zoom;
+        header[101] = self.max_zoom;
+        header[102..106].copy_from_slice(&lon_e7(self.bounding_box.west).to_le_bytes());
+        header[106..110].copy_from_slice(&lat_e7(self.bounding_box.south).to_le_bytes());
+        header[110..114].copy_from_slice(&lon_e7(self.bounding_box.east).to_le_bytes());
+        header[114..118].copy_from_slice(&lat_e7(self.bounding_box.north).to_le_bytes());
+        header[118] = self.min_zoom;
+        header[119..123].copy_from_slice(&lon_e7(self.bounding_box.west).to_le_bytes());
+        header[123..127].copy_from_slice(&lat_e7(self.bounding_box.north).to_le_bytes());
+
+        header
+    }
+}
+
+struct HeaderLayout {
+    root_dir_offset: u64,
+    root_dir_length: u64,
+    json_metadata_offset: u64,
+    json_metadata_length: u64,
+    leaf_dirs_offset: u64,
+    leaf_dirs_length: u64,
+    tile_data_offset: u64,
+    tile_data_length: u64,
+    addressed_tiles_count: u64,
+    tile_entries_count: u64,
+    tile_contents_count: u64,
+}
+
+fn lon_e7(rad: f64) -> i32 {
+    (rad.to_degrees() * 1e7) as i32
+}
+
+fn lat_e7(rad: f64) -> i32 {
+    (rad.to_degrees() * 1e7) as i32
+}
+
+/// Collapses consecutive entries that share the same blob into a single
+/// directory entry with `run_length > 1`, as the spec intends for ranges of
+/// identical tiles.
+fn merge_runs(sorted: &[&DirEntry]) -> Vec<DirEntry> {
+    let mut merged: Vec<DirEntry> = Vec::new();
+
+    for entry in sorted {
+        if let Some(last) = merged.last_mut() {
+            let contiguous = last.tile_id + last.run_length as u64 == entry.tile_id;
+            if contiguous && last.offset == entry.offset && last.length == entry.length {
+                last.run_length += 1;
+                continue;
+            }
+        }
+
+        merged.push(DirEntry {
+            tile_id: entry.tile_id,
+            offset: entry.offset,
+            length: entry.length,
+            run_length: 1,
+        });
+    }
+
+    merged
+}
+
+/// Serializes a directory as parallel varint arrays (tile_id deltas,
+/// run lengths, lengths, offsets), the layout PMTiles v3 directories use.
+fn serialize_directory(entries: &[DirEntry]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_varint(&mut buf, entries.len() as u64);
+
+    let mut last_id = 0u64;
+    for entry in entries {
+        write_varint(&mut buf, entry.tile_id - last_id);
+        last_id = entry.tile_id;
+    }
+
+    for entry in entries {
+        write_varint(&mut buf, entry.run_length as u64);
+    }
+
+    for entry in entries {
+        write_varint(&mut buf, entry.length as u64);
+    }
+
+    let mut expected_offset = None;
+    for entry in entries {
+        if expected_offset == Some(entry.offset) {
+            write_varint(&mut buf, 0);
+        } else {
+            write_varint(&mut buf, entry.offset + 1);
+        }
+        expected_offset = Some(entry.offset + entry.length as u64);
+    }
+
+    buf
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value != 0 {
+            buf.push(byte | 0x80);
+        } else {
+            buf.push(byte);
+            break;
+        }
+    }
+}
+
+fn gzip(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).context("failed gzip-compressing pmtiles section")?;
+    encoder.finish().context("failed finishing gzip stream")
+}
+
+/// Maps a `(z, x, y)` tile to its position along a Hilbert space-filling
+/// curve, with tiles ordered by zoom level first: the PMTiles `tile_id`.
+///
+/// ref: https://github.com/protomaps/PMTiles/blob/main/spec/v3/spec.md#6-tile_id-calculation
+pub fn tile_id(z: u8, x: usize, y: usize) -> u64 {
+    let mut acc = 0u64;
+    for level in 0..z {
+        acc += 1u64 << (2 * level as u32);
+    }
+
+    acc + hilbert_xy_to_index(z, x, y)
+}
+
+fn hilbert_xy_to_index(z: u8, mut x: usize, mut y: usize) -> u64 {
+    let n = 1usize << z;
+    let mut d = 0u64;
+
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = if (x & s) > 0 { 1 } else { 0 };
+        let ry = if (y & s) > 0 { 1 } else { 0 };
+
+        d += (s * s) as u64 * ((3 * rx) ^ ry) as u64;
+        rotate(n, &mut x, &mut y, rx, ry);
+
+        s /= 2;
+    }
+
+    d
+}
+
+fn rotate(n: usize, x: &mut usize, y: &mut usize, rx: usize, ry: usize) {
+    if ry == 0 {
+        if rx == 1 {
+            *x = n - 1 - *x;
+            *y = n - 1 - *y;
+        }
+
+        std::mem::swap(x, y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_id_of_root_tile_is_zero() {
+        assert_eq!(tile_id(0, 0, 0), 0);
+    }
+
+    #[test]
+    fn tile_id_offsets_by_tile_count_of_prior_levels() {
+        // level 0 contributes 1 tile, so every z=1 id starts right after it
+        assert_eq!(tile_id(1, 0, 0), 1);
+        // levels 0 and 1 contribute 1 + 4 = 5 tiles, so z=2 ids start at 5
+        assert_eq!(tile_id(2, 0, 0), 5);
+    }
+
+    #[test]
+    fn tile_id_follows_hilbert_curve_order_within_a_level() {
+        let ids = [(0, 0), (0, 1), (1, 1), (1, 0)].map(|(x, y)| tile_id(1, x, y));
+        assert_eq!(ids, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn tile_id_is_unique_per_tile_within_a_level() {
+        let n = 1usize << 4;
+        let mut ids: Vec<u64> = (0..n)
+            .flat_map(|x| (0..n).map(move |y| tile_id(4, x, y)))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+
+        assert_eq!(ids.len(), n * n);
+    }
+}
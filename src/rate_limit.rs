@@ -0,0 +1,24 @@
+use crate::token_bucket::TokenBucket;
+
+/// A shared token-bucket limiter capping how many tile requests are issued
+/// per second in aggregate, independent of how many fetches run in
+/// parallel. `fetch_rate` only bounds concurrency, so e.g. 30 parallel
+/// workers could otherwise still collectively hammer a tile server far
+/// faster than intended.
+///
+/// The bucket starts full, allowing a one-second burst before throttling
+/// kicks in.
+#[derive(Debug)]
+pub struct RateLimiter(TokenBucket);
+
+impl RateLimiter {
+    /// Creates a limiter capping request throughput at `requests_per_sec`.
+    pub fn new(requests_per_sec: f64) -> Self {
+        Self(TokenBucket::new(requests_per_sec))
+    }
+
+    /// Waits until budget for one more request is available, then consumes it.
+    pub async fn acquire(&self) {
+        self.0.acquire(1.0).await
+    }
+}
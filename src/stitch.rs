@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+use crate::bounding_box::BoundingBox;
+use crate::tile::{fractional_tile_coord, Tile};
+
+const TILE_SIZE: u32 = 256;
+
+/// Assembles the tiles of selected zoom levels into one composite PNG each,
+/// covering the bounding box, similar to how "dezoomer" tools reconstruct a
+/// full picture from a tile grid.
+///
+/// Tiles are blitted onto their zoom level's canvas as they're fetched (see
+/// [`FetchOptions::stitcher`](crate::FetchOptions::stitcher)), rather than
+/// being buffered in memory until the whole zoom level completes. Only tiles
+/// actually downloaded in this run are captured; a tile skipped because it
+/// already exists on disk (`--fetch-existing` unset) won't appear in the
+/// stitched image.
+#[derive(Debug)]
+pub struct Stitcher {
+    bounding_box: BoundingBox,
+    output_dir: PathBuf,
+    canvases: Mutex<HashMap<u8, Canvas>>,
+}
+
+#[derive(Debug)]
+struct Canvas {
+    /// North-west corner tile, i.e. the tile whose top-left pixel maps to
+    /// `image`'s origin.
+    nw: Tile,
+    image: RgbaImage,
+}
+
+impl Stitcher {
+    /// Creates a stitcher that assembles the given zoom levels, writing the
+    /// result to `{output_dir}/stitched-z{zoom}.png` once [`finish`](Self::finish)
+    /// is called.
+    pub fn new(bounding_box: BoundingBox, output_dir: PathBuf, zoom_levels: &[u8]) -> Self {
+        let canvases = zoom_levels
+            .iter()
+            .map(|&zoom| (zoom, Canvas::new(bounding_box, zoom)))
+            .collect();
+
+        Self {
+            bounding_box,
+            output_dir,
+            canvases: Mutex::new(canvases),
+        }
+    }
+
+    /// Decodes a freshly-fetched tile and blits it onto its zoom level's
+    /// canvas, if that zoom is being stitched. A no-op for zooms that
+    /// weren't passed to [`new`](Self::new).
+    pub fn add_tile(&self, tile: &Tile, bytes: &[u8]) -> Result<()> {
+        let mut canvases = self.canvases.lock().unwrap();
+        let canvas = match canvases.get_mut(&tile.z) {
+            Some(canvas) => canvas,
+            None => return Ok(()),
+        };
+
+        let decoded = image::load_from_memory(bytes)
+            .with_context(|| {
+                format!("failed decoding tile {}x{}x{} for stitching", tile.x, tile.y, tile.z)
+            })?
+            .to_rgba();
+
+        let x = (tile.x - canvas.nw.x) as u32 * TILE_SIZE;
+        let y = (tile.y - canvas.nw.y) as u32 * TILE_SIZE;
+        image::imageops::overlay(&mut canvas.image, &decoded, x, y);
+
+        Ok(())
+    }
+
+    /// Crops each zoom's canvas down from whole-tile boundaries to the
+    /// bounding box's exact pixel bounds and writes it to
+    /// `{output_dir}/stitched-z{zoom}.png`.
+    pub async fn finish(&self) -> Result<()> {
+        let encoded: Vec<(u8, Vec<u8>)> = {
+            let canvases = self.canvases.lock().unwrap();
+
+            canvases
+                .iter()
+                .map(|(&zoom, canvas)| {
+                    let cropped = canvas.crop_to_bbox(&self.bounding_box, zoom);
+                    let mut png = Vec::new();
+                    cropped
+                        .write_to(&mut png, image::ImageOutputFormat::Png)
+                        .with_context(|| format!("failed encoding stitched image for zoom {}", zoom))?;
+                    Ok((zoom, png))
+                })
+                .collect::<Result<_>>()?
+        };
+
+        for (zoom, png) in encoded {
+            let path = self.output_dir.join(format!("stitched-z{}.png", zoom));
+            tokio::fs::write(&path, png)
+                .await
+                .with_context(|| format!("failed writing stitched image to {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Canvas {
+    fn new(bounding_box: BoundingBox, zoom: u8) -> Self {
+        let (nw, se) = bounding_box.tile_bounds(zoom);
+        let width = (se.x - nw.x + 1) as u32 * TILE_SIZE;
+        let height = (se.y - nw.y + 1) as u32 * TILE_SIZE;
+
+        Self {
+            nw,
+            image: RgbaImage::new(width, height),
+        }
+    }
+
+    /// Crops the canvas from whole-tile boundaries down to the exact pixel
+    /// bounds of `bounding_box`, using the fractional Web-Mercator
+    /// coordinate of its corners.
+    fn crop_to_bbox(&self, bounding_box: &BoundingBox, zoom: u8) -> RgbaImage {
+        let (nw_x, nw_y) = fractional_tile_coord(bounding_box.north, bounding_box.west, zoom);
+        let (se_x, se_y) = fractional_tile_coord(bounding_box.south, bounding_box.east, zoom);
+
+        let to_px = |tile_coord: f64| ((tile_coord - self.nw.x as f64) * TILE_SIZE as f64).round() as u32;
+        let to_py = |tile_coord: f64| ((tile_coord - self.nw.y as f64) * TILE_SIZE as f64).round() as u32;
+
+        let left = to_px(nw_x);
+        let top = to_py(nw_y);
+        let width = to_px(se_x).saturating_sub(left).max(1).min(self.image.width() - left);
+        let height = to_py(se_y).saturating_sub(top).max(1).min(self.image.height() - top);
+
+        image::imageops::crop_imm(&self.image, left, top, width, height).to_image()
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::{fmt, path::PathBuf, sync::Arc};
+use tokio::{fs, io::AsyncWriteExt};
+
+use super::TileStore;
+use crate::tile::Tile;
+
+/// Writes tiles to the local filesystem, laid out as `{output_folder}/z/x/y.png`
+/// unless overridden via [`with_path_fn`](Self::with_path_fn).
+#[derive(Clone)]
+pub struct LocalStore {
+    output_folder: PathBuf,
+    path_fn: Option<Arc<dyn Fn(&Tile) -> PathBuf + Send + Sync>>,
+}
+
+impl LocalStore {
+    /// Creates a store rooted at the given folder. The folder is created
+    /// lazily, when the first tile or partial copy is written.
+    pub fn new(output_folder: PathBuf) -> Self {
+        Self {
+            output_folder,
+            path_fn: None,
+        }
+    }
+
+    /// Overrides the default `{z}/{x}/{y}.png` layout with a custom
+    /// function from tile to output path, e.g. to bucket tiles by region or
+    /// flatten them into a single directory with encoded names.
+    ///
+    /// The returned path is used as-is; it isn't joined onto
+    /// `output_folder`, so the closure is free to ignore it entirely.
+    pub fn with_path_fn(mut self, f: impl Fn(&Tile) -> PathBuf + Send + Sync + 'static) -> Self {
+        self.path_fn = Some(Arc::new(f));
+        self
+    }
+
+    fn path_for(&self, tile: &Tile) -> PathBuf {
+        if let Some(path_fn) = &self.path_fn {
+            return path_fn(tile);
+        }
+
+        let mut target = self.output_folder.join(tile.z.to_string());
+        target.push(tile.x.to_string());
+        target.push(format!("{}.png", tile.y));
+        target
+    }
+
+    /// The in-progress copy of a tile, written to before the final path is
+    /// populated via an atomic rename. A lone leftover `.partial` file means
+    /// a previous attempt was interrupted before completing.
+    fn partial_path_for(&self, tile: &Tile) -> PathBuf {
+        let mut name = self.path_for(tile).into_os_string();
+        name.push(".partial");
+        name.into()
+    }
+}
+
+#[async_trait]
+impl TileStore for LocalStore {
+    async fn exists(&self, tile: &Tile) -> Result<bool> {
+        Ok(self.path_for(tile).exists())
+    }
+
+    async fn put(&self, tile: &Tile, bytes: Bytes) -> Result<()> {
+        let output_file = self.path_for(tile);
+        let partial_file = self.partial_path_for(tile);
+
+        if let Some(parent) = output_file.parent() {
+            fs::create_dir_all(parent).await.with_context(|| {
+                format!(
+                    "failed creating output directory for tile {}x{}x{}",
+                    tile.x, tile.y, tile.z
+                )
+            })?;
+        }
+
+        fs::write(&partial_file, &bytes).await.with_context(|| {
+            format!(
+                "failed writing tile {}x{}x{} to {}",
+                tile.x,
+                tile.y,
+                tile.z,
+                partial_file.display()
+            )
+        })?;
+
+        fs::rename(&partial_file, &output_file)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed committing tile {}x{}x{} to {}",
+                    tile.x,
+                    tile.y,
+                    tile.z,
+                    output_file.display()
+                )
+            })?;
+
+        Ok(())
+    }
+
+    async fn partial_len(&self, tile: &Tile) -> Result<u64> {
+        match fs::metadata(self.partial_path_for(tile)).await {
+            Ok(meta) => Ok(meta.len()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e).with_context(|| {
+                format!(
+                    "failed checking partial download size for tile {}x{}x{}",
+                    tile.x, tile.y, tile.z
+                )
+            }),
+        }
+    }
+
+    async fn read_partial(&self, tile: &Tile) -> Result<Bytes> {
+        match fs::read(self.partial_path_for(tile)).await {
+            Ok(bytes) => Ok(Bytes::from(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Bytes::new()),
+            Err(e) => Err(e).with_context(|| {
+                format!(
+                    "failed reading partial download for tile {}x{}x{}",
+                    tile.x, tile.y, tile.z
+                )
+            }),
+        }
+    }
+
+    async fn truncate_partial(&self, tile: &Tile, len: u64) -> Result<()> {
+        let partial_file = self.partial_path_for(tile);
+
+        if let Some(parent) = partial_file.parent() {
+            fs::create_dir_all(parent).await.with_context(|| {
+                format!(
+                    "failed creating output directory for tile {}x{}x{}",
+                    tile.x, tile.y, tile.z
+                )
+            })?;
+        }
+
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&partial_file)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed opening partial download for tile {}x{}x{} at {}",
+                    tile.x,
+                    tile.y,
+                    tile.z,
+                    partial_file.display()
+                )
+            })?;
+
+        file.set_len(len).await.with_context(|| {
+            format!(
+                "failed truncating partial download for tile {}x{}x{}",
+                tile.x, tile.y, tile.z
+            )
+        })
+    }
+
+    async fn append_partial(&self, tile: &Tile, chunk: &[u8]) -> Result<()> {
+        let partial_file = self.partial_path_for(tile);
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&partial_file)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed opening partial download for tile {}x{}x{} at {}",
+                    tile.x,
+                    tile.y,
+                    tile.z,
+                    partial_file.display()
+                )
+            })?;
+
+        file.write_all(chunk).await.with_context(|| {
+            format!(
+                "failed appending to partial download for tile {}x{}x{}",
+                tile.x, tile.y, tile.z
+            )
+        })
+    }
+}
+
+impl fmt::Debug for LocalStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalStore")
+            .field("output_folder", &self.output_folder)
+            .field("path_fn", &self.path_fn.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
@@ -0,0 +1,126 @@
+mod local;
+mod pmtiles;
+mod s3;
+
+pub use local::LocalStore;
+pub use pmtiles::PmTilesStore;
+pub use s3::S3Store;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::fmt::Debug;
+
+use crate::bounding_box::BoundingBox;
+use crate::tile::Tile;
+
+/// A destination tiles can be written to and checked for presence in.
+///
+/// Implementations back the `--output` CLI argument / [`Config::store`](crate::Config::store):
+/// the local filesystem layout (`z/x/y.png`) is just the default [`LocalStore`]
+/// implementation, swappable for e.g. [`S3Store`] without touching the fetch
+/// pipeline.
+#[async_trait]
+pub trait TileStore: Debug + Send + Sync {
+    /// Returns whether the given tile has already been written to the store.
+    async fn exists(&self, tile: &Tile) -> Result<bool>;
+
+    /// Writes the given tile's encoded bytes to the store, overwriting any
+    /// previous contents.
+    async fn put(&self, tile: &Tile, bytes: Bytes) -> Result<()>;
+
+    /// Called once after every tile in the job has been fetched, so stores
+    /// that buffer their output (e.g. a single-file archive) can flush it.
+    ///
+    /// The default implementation is a no-op, since most stores write each
+    /// tile as it arrives.
+    async fn finalize(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The number of bytes already written to a tile's in-progress partial
+    /// copy left over from a previous, interrupted attempt, if the store
+    /// keeps one. Used to resume the download via a `Range` request instead
+    /// of starting over.
+    ///
+    /// The default implementation always returns `0`, for stores (S3,
+    /// PMTiles) that don't keep a resumable partial artifact.
+    async fn partial_len(&self, _tile: &Tile) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// The bytes already written to a tile's partial copy, as reported by
+    /// [`partial_len`](TileStore::partial_len). Only called when
+    /// `partial_len` returned a non-zero count.
+    async fn read_partial(&self, _tile: &Tile) -> Result<Bytes> {
+        Ok(Bytes::new())
+    }
+
+    /// Truncates (creating it first if necessary) a tile's partial copy to
+    /// exactly `len` bytes, discarding anything written past that point by a
+    /// previous, now-abandoned attempt. Called before each attempt starts
+    /// streaming, with `len` set to the prefix this attempt's response
+    /// actually covers (`0` unless the request resumed from a prior partial
+    /// copy).
+    ///
+    /// The default implementation is a no-op, for stores that don't keep a
+    /// resumable partial artifact.
+    async fn truncate_partial(&self, _tile: &Tile, _len: u64) -> Result<()> {
+        Ok(())
+    }
+
+    /// Appends a freshly-received chunk to a tile's partial copy as it
+    /// arrives, so a process killed mid-download leaves a resumable file
+    /// behind instead of losing everything buffered in memory.
+    ///
+    /// The default implementation is a no-op, for stores (S3, PMTiles) that
+    /// only ever write a tile's bytes in full via [`put`](TileStore::put).
+    async fn append_partial(&self, _tile: &Tile, _chunk: &[u8]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Distinguishes the on-disk shape [`TileStore::put`] writes tiles in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// One file per tile, laid out as `{z}/{x}/{y}.png` (or the S3-key
+    /// equivalent).
+    Directory,
+    /// All tiles packed into a single PMTiles v3 archive.
+    PmTiles,
+}
+
+/// Parses the `--output` argument into the [`TileStore`] it refers to, along
+/// with the [`OutputFormat`] that store produces.
+///
+/// Recognizes `s3://bucket/key/{z}/{x}/{y}.png` URLs and constructs an
+/// [`S3Store`]; a path ending in `.pmtiles` constructs a [`PmTilesStore`];
+/// anything else is treated as a local filesystem path.
+pub fn parse_store(
+    output: &str,
+    bounding_box: BoundingBox,
+    min_zoom: u8,
+    max_zoom: u8,
+) -> Result<(Box<dyn TileStore>, OutputFormat)> {
+    if let Some(rest) = output.strip_prefix("s3://") {
+        return Ok((Box::new(S3Store::from_url_rest(rest)?), OutputFormat::Directory));
+    }
+
+    if output.ends_with(".pmtiles") {
+        let store = PmTilesStore::new(output.into(), bounding_box, min_zoom, max_zoom);
+        return Ok((Box::new(store), OutputFormat::PmTiles));
+    }
+
+    Ok((Box::new(LocalStore::new(output.into())), OutputFormat::Directory))
+}
+
+/// Whether `--output` addresses a plain local directory, as opposed to an
+/// `s3://` bucket or a `.pmtiles` archive file.
+///
+/// The job manifest and validator index are rooted at `--output` by joining
+/// a sidecar file name onto it, which only makes sense when `--output` is
+/// itself a directory: joining a file name onto an S3 URL or a `.pmtiles`
+/// archive path wouldn't address anything a store could read back.
+pub fn is_local_output(output: &str) -> bool {
+    !output.starts_with("s3://") && !output.ends_with(".pmtiles")
+}
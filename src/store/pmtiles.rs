@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::{path::PathBuf, sync::Mutex};
+use tokio::fs;
+
+use super::TileStore;
+use crate::bounding_box::BoundingBox;
+use crate::pmtiles::PmTilesWriter;
+use crate::tile::Tile;
+
+/// Buffers fetched tiles in memory and packs them into a single `.pmtiles`
+/// v3 archive on [`finalize`](TileStore::finalize), instead of writing one
+/// file per tile to a directory tree.
+///
+/// Since the archive is only assembled once the whole job finishes,
+/// [`exists`](TileStore::exists) always reports tiles as missing: there's no
+/// partial archive on disk to check against mid-run.
+#[derive(Debug)]
+pub struct PmTilesStore {
+    path: PathBuf,
+    writer: Mutex<PmTilesWriter>,
+}
+
+impl PmTilesStore {
+    pub fn new(path: PathBuf, bounding_box: BoundingBox, min_zoom: u8, max_zoom: u8) -> Self {
+        Self {
+            path,
+            writer: Mutex::new(PmTilesWriter::new(bounding_box, min_zoom, max_zoom)),
+        }
+    }
+}
+
+#[async_trait]
+impl TileStore for PmTilesStore {
+    async fn exists(&self, _tile: &Tile) -> Result<bool> {
+        Ok(false)
+    }
+
+    async fn put(&self, tile: &Tile, bytes: Bytes) -> Result<()> {
+        self.writer.lock().unwrap().add_tile(tile, &bytes);
+        Ok(())
+    }
+
+    async fn finalize(&self) -> Result<()> {
+        let archive = self.writer.lock().unwrap().encode()?;
+
+        fs::write(&self.path, archive)
+            .await
+            .with_context(|| format!("failed writing pmtiles archive to {}", self.path.display()))
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use maplit::hashmap;
+use rusoto_core::{Region, RusotoError};
+use rusoto_s3::{HeadObjectRequest, PutObjectRequest, S3Client, S3};
+use std::str::FromStr;
+use strfmt::strfmt;
+
+use super::TileStore;
+use crate::tile::Tile;
+
+const DEFAULT_KEY_TEMPLATE: &str = "{z}/{x}/{y}.png";
+
+/// Writes tiles to an S3-compatible object store, addressing them via
+/// `s3://bucket/key/{z}/{x}/{y}.png`-style URLs.
+///
+/// Authenticates through the standard AWS credential provider chain
+/// (environment variables, then the shared credentials file, then EC2/ECS
+/// instance metadata), same as the AWS CLI and SDKs.
+#[derive(Debug)]
+pub struct S3Store {
+    bucket: String,
+    key_template: String,
+    client: S3Client,
+}
+
+impl S3Store {
+    /// Parses the portion of an `s3://` URL following the scheme, i.e.
+    /// `bucket/key/{z}/{x}/{y}.png`, into an [`S3Store`].
+    ///
+    /// The region is taken from the `AWS_REGION` / `AWS_DEFAULT_REGION`
+    /// environment variables, falling back to `us-east-1`.
+    pub fn from_url_rest(rest: &str) -> Result<Self> {
+        let (bucket, key_template) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx + 1..]),
+            None => (rest, ""),
+        };
+
+        if bucket.is_empty() {
+            anyhow::bail!("s3:// output URLs must specify a bucket, e.g. s3://my-bucket/tiles");
+        }
+
+        let key_template = if key_template.is_empty() {
+            DEFAULT_KEY_TEMPLATE.to_owned()
+        } else if key_template.contains("{x}") {
+            key_template.to_owned()
+        } else {
+            format!("{}/{}", key_template.trim_end_matches('/'), DEFAULT_KEY_TEMPLATE)
+        };
+
+        let region = std::env::var("AWS_REGION")
+            .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+            .ok()
+            .and_then(|r| Region::from_str(&r).ok())
+            .unwrap_or(Region::UsEast1);
+
+        Ok(Self {
+            bucket: bucket.to_owned(),
+            key_template,
+            client: S3Client::new(region),
+        })
+    }
+
+    fn key_for(&self, tile: &Tile) -> Result<String> {
+        let vars = hashmap! {
+            "x".to_owned() => tile.x.to_string(),
+            "y".to_owned() => tile.y.to_string(),
+            "z".to_owned() => tile.z.to_string(),
+        };
+
+        strfmt(&self.key_template, &vars).context("failed formatting S3 key")
+    }
+}
+
+#[async_trait]
+impl TileStore for S3Store {
+    async fn exists(&self, tile: &Tile) -> Result<bool> {
+        let key = self.key_for(tile)?;
+
+        let res = self
+            .client
+            .head_object(HeadObjectRequest {
+                bucket: self.bucket.clone(),
+                key,
+                ..Default::default()
+            })
+            .await;
+
+        match res {
+            Ok(_) => Ok(true),
+            Err(RusotoError::Unknown(ref resp)) if resp.status == 404 => Ok(false),
+            Err(e) => Err(e).context("failed checking for existing tile in S3"),
+        }
+    }
+
+    async fn put(&self, tile: &Tile, bytes: Bytes) -> Result<()> {
+        let key = self.key_for(tile)?;
+
+        self.client
+            .put_object(PutObjectRequest {
+                bucket: self.bucket.clone(),
+                key,
+                body: Some(bytes.to_vec().into()),
+                content_type: Some("image/png".to_owned()),
+                ..Default::default()
+            })
+            .await
+            .with_context(|| {
+                format!("failed uploading tile {}x{}x{} to S3", tile.x, tile.y, tile.z)
+            })?;
+
+        Ok(())
+    }
+}
@@ -1,10 +1,16 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use bytes::BytesMut;
 use futures::prelude::*;
 use reqwest::StatusCode;
-use std::{f64::consts::PI, path::Path, time::Duration};
-use tokio::fs;
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::PI, time::Duration};
 
-use crate::fetch::BACKOFF_DELAY;
+use crate::backoff::DecorrelatedJitter;
+use crate::bandwidth::Throttle;
+use crate::host_limit::HostLimiter;
+use crate::index::ValidatorIndex;
+use crate::stitch::Stitcher;
+use crate::store::TileStore;
 use crate::url::UrlFormat;
 
 const LAT_MIN: f64 = -85_f64 / 180_f64 * PI;
@@ -14,13 +20,41 @@ const LON_MAX: f64 = PI;
 
 /// An OSM slippy-map tile with x, y and z-coordinate.
 /// ref: https://wiki.openstreetmap.org/wiki/Slippy_map_tilenames
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct Tile {
     pub x: usize,
     pub y: usize,
     pub z: u8,
 }
 
+/// A per-tile download outcome, passed to
+/// [`Config::on_tile_event`](crate::Config::on_tile_event) after every fetch
+/// attempt so library users can drive their own progress UI instead of the
+/// built-in bar.
+#[derive(Clone, Debug)]
+pub enum TileEvent {
+    /// The tile was fetched, or skipped because it already existed /
+    /// wasn't modified. `bytes` is `0` in the latter two cases.
+    Success { tile: Tile, bytes: u64 },
+    /// The tile failed after exhausting all retry attempts.
+    Failure { tile: Tile, message: String },
+}
+
+/// Knobs for [`Tile::fetch_from`], bundled up since the fetch driver threads
+/// the same values through for every tile in the job.
+pub struct FetchOptions<'a> {
+    pub store: &'a dyn TileStore,
+    pub fetch_existing: bool,
+    pub revalidation: Option<&'a ValidatorIndex>,
+    pub bandwidth: Option<&'a Throttle>,
+    pub host_limiter: Option<&'a HostLimiter>,
+    /// When set, freshly-fetched tiles are also blitted onto this zoom
+    /// level's composite image as they arrive.
+    pub stitcher: Option<&'a Stitcher>,
+    /// Maximum number of attempts (including the first) before giving up.
+    pub max_attempts: u8,
+}
+
 impl Tile {
     pub fn new(x: usize, y: usize, z: u8) -> Self {
         Self { x, y, z }
@@ -31,89 +65,258 @@ impl Tile {
         assert!(lat_rad >= LAT_MIN && lat_rad <= LAT_MAX);
         assert!(lon_rad >= LON_MIN && lon_rad <= LON_MAX);
 
-        // scale factor
-        let n = 2_f64.powi(zoom as i32);
-
-        let lon_deg = lon_rad * 180_f64 / PI;
-
-        let x = (lon_deg + 180_f64) / 360_f64 * n;
-        let y = (1_f64 - lat_rad.tan().asinh() / PI) / 2_f64 * n;
+        let (x, y) = fractional_tile_coord(lat_rad, lon_rad, zoom);
 
         Self::new(x as usize, y as usize, zoom)
     }
 
-    /// Fetches the given tile from the given URL using the given HTTP client.
+    /// Fetches the given tile from the given URL using the given HTTP client,
+    /// writing the response through the configured [`TileStore`]. Returns the
+    /// number of bytes transferred.
+    ///
+    /// Makes at most `opts.max_attempts` attempts in total, retrying
+    /// connection errors, 5xx responses and `429`s with
+    /// decorrelated-jitter backoff unless the
+    /// server sends an explicit `Retry-After`. Requests to the same resolved
+    /// host are capped by `opts.host_limiter`, since OSM-style `{s}`
+    /// subdomains all resolve to one origin.
+    ///
+    /// If `opts.store` reports a leftover partial copy from a prior
+    /// interrupted attempt, resumes it via a `Range` request rather than
+    /// re-downloading from scratch. A response whose `Content-Length`
+    /// doesn't match the bytes actually received is treated as a failed
+    /// attempt and retried.
     pub async fn fetch_from(
         &self,
         client: &reqwest::Client,
         url_fmt: &UrlFormat,
-        output_folder: &Path,
-        fetch_existing: bool,
-    ) -> Result<()> {
+        opts: &FetchOptions<'_>,
+    ) -> Result<u64> {
         let formatted_url = url_fmt.tile_url(&self)?;
+        let validators = opts
+            .revalidation
+            .map(|idx| idx.get(self))
+            .filter(|(etag, last_modified)| etag.is_some() || last_modified.is_some());
 
-        let output_file = {
-            let mut target = output_folder.join(self.z.to_string());
-            target.push(self.x.to_string());
-            fs::create_dir_all(&target).await.with_context(|| {
-                format!(
-                    "failed creating output directory for tile {}x{}x{}",
-                    self.x, self.y, self.z
-                )
-            })?;
-            target.push(format!("{}.png", self.y));
+        // without a stored validator to revalidate against, fall back to the
+        // cheap existence check
+        if validators.is_none() && !opts.fetch_existing && opts.store.exists(self).await? {
+            return Ok(0);
+        }
 
-            target
+        let host = host_of(&formatted_url);
+        let host_semaphore = match opts.host_limiter {
+            Some(limiter) => Some(limiter.semaphore_for(host).await),
+            None => None,
         };
+        let mut backoff = DecorrelatedJitter::new();
 
-        // if the tile's already been downloaded, skip it
-        if !fetch_existing && output_file.exists() {
-            return Ok(());
-        }
+        // bytes of a `.partial` copy left over from a previous, interrupted
+        // attempt at this tile; resumed via a `Range` request below instead
+        // of being re-downloaded from scratch
+        let resume_from = opts.store.partial_len(self).await?;
+
+        let mut attempt = 0u8;
+        let (bytes, resumed_len) = loop {
+            attempt += 1;
+
+            let _permit = match &host_semaphore {
+                Some(sem) => Some(sem.acquire().await),
+                None => None,
+            };
+
+            let mut request = client.get(&formatted_url);
+            if let Some((etag, last_modified)) = &validators {
+                if let Some(etag) = etag {
+                    request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = last_modified {
+                    request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+                }
+            }
+            if resume_from > 0 {
+                request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+            }
+
+            let raw_response = match request.send().await {
+                Ok(r) => r,
+                Err(_) if attempt < opts.max_attempts => {
+                    tokio::time::delay_for(backoff.next()).await;
+                    continue;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("failed fetching tile {}x{}x{}", self.x, self.y, self.z)
+                    })
+                }
+            };
+
+            if raw_response.status() == StatusCode::NOT_MODIFIED {
+                return Ok(0);
+            }
+
+            let retryable =
+                raw_response.status() == StatusCode::TOO_MANY_REQUESTS || raw_response.status().is_server_error();
 
-        let mut response_reader = loop {
-            let raw_response =
-                client.get(&formatted_url).send().await.with_context(|| {
-                    format!("failed fetching tile {}x{}x{}", self.x, self.y, self.z)
-                })?;
+            if retryable {
+                if attempt >= opts.max_attempts {
+                    return Err(anyhow!(
+                        "tile {}x{}x{} failed after {} attempts with status {}",
+                        self.x,
+                        self.y,
+                        self.z,
+                        attempt,
+                        raw_response.status()
+                    ));
+                }
 
-            if raw_response.status() == StatusCode::TOO_MANY_REQUESTS {
-                let retry_after = raw_response
+                let delay = raw_response
                     .headers()
                     .get("Retry-After")
                     .and_then(|v| v.to_str().ok())
                     .and_then(|val| val.parse::<u64>().ok())
                     .map(Duration::from_secs)
-                    .unwrap_or(BACKOFF_DELAY);
+                    .unwrap_or_else(|| backoff.next());
 
-                tokio::time::delay_for(retry_after).await;
+                tokio::time::delay_for(delay).await;
                 continue;
             }
 
-            let response_stream = raw_response
-                .error_for_status()
-                .with_context(|| {
-                    format!(
-                        "received invalid status code fetching tile {}x{}x{}",
-                        self.x, self.y, self.z
-                    )
-                })?
-                .bytes_stream()
-                .map_err(|e| tokio::io::Error::new(tokio::io::ErrorKind::Other, e));
-
-            break tokio::io::stream_reader(response_stream);
-        };
-
-        let mut output_file = tokio::fs::File::create(output_file).await?;
-        tokio::io::copy(&mut response_reader, &mut output_file)
-            .await
-            .with_context(|| {
+            let response = raw_response.error_for_status().with_context(|| {
                 format!(
-                    "failed streaming tile {}x{}x{} to disk",
+                    "received invalid status code fetching tile {}x{}x{}",
                     self.x, self.y, self.z
                 )
             })?;
 
-        Ok(())
+            if let Some(idx) = opts.revalidation {
+                let etag = header_str(response.headers(), reqwest::header::ETAG);
+                let last_modified = header_str(response.headers(), reqwest::header::LAST_MODIFIED);
+                idx.set(self, etag, last_modified);
+            }
+
+            // the server only honors the `Range` request if it answers with
+            // `206 Partial Content`; anything else (including a plain `200`)
+            // means it sent the whole tile and any partial copy is stale
+            let resumed = resume_from > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+            let content_length = response.content_length();
+
+            // drop anything a previous, now-abandoned attempt appended past
+            // the prefix this attempt's response actually covers, so the
+            // partial copy on disk stays a true prefix of `buf` as we stream
+            let truncate_to = if resumed { resume_from } else { 0 };
+            opts.store.truncate_partial(self, truncate_to).await?;
+
+            let mut buf = BytesMut::new();
+            if resumed {
+                buf.extend_from_slice(&opts.store.read_partial(self).await?);
+            }
+
+            let mut body = response.bytes_stream();
+            let mut stream_failed = false;
+            while let Some(chunk) = body.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(_) if attempt < opts.max_attempts => {
+                        stream_failed = true;
+                        break;
+                    }
+                    Err(e) => {
+                        return Err(e).with_context(|| {
+                            format!("failed streaming tile {}x{}x{} to disk", self.x, self.y, self.z)
+                        })
+                    }
+                };
+
+                if let Some(throttle) = opts.bandwidth {
+                    throttle.acquire(chunk.len() as u64).await;
+                }
+
+                // written incrementally so a process killed mid-download
+                // leaves a resumable `.partial` file behind instead of
+                // losing everything still sitting in `buf`
+                opts.store.append_partial(self, &chunk).await?;
+
+                buf.extend_from_slice(&chunk);
+            }
+
+            if stream_failed {
+                tokio::time::delay_for(backoff.next()).await;
+                continue;
+            }
+
+            if let Some(len) = content_length {
+                let expected = if resumed { resume_from + len } else { len };
+
+                if buf.len() as u64 != expected {
+                    if attempt < opts.max_attempts {
+                        tokio::time::delay_for(backoff.next()).await;
+                        continue;
+                    }
+
+                    return Err(anyhow!(
+                        "tile {}x{}x{} response ended after {} bytes, expected {}",
+                        self.x,
+                        self.y,
+                        self.z,
+                        buf.len(),
+                        expected
+                    ));
+                }
+            }
+
+            break (buf.freeze(), truncate_to);
+        };
+
+        // the resumed prefix was read back from the partial copy, not
+        // transferred by this call
+        let len = bytes.len() as u64 - resumed_len;
+
+        if let Some(stitcher) = opts.stitcher {
+            stitcher.add_tile(self, &bytes)?;
+        }
+
+        opts.store.put(self, bytes).await?;
+
+        Ok(len)
+    }
+}
+
+/// The continuous (fractional) Web-Mercator tile coordinate of a lat/lon pair
+/// at the given zoom, before it's floored down to an integer [`Tile`].
+/// Shared with [`crate::stitch`], which needs the sub-tile remainder to crop
+/// a stitched image down to the bounding box's exact pixel bounds.
+pub(crate) fn fractional_tile_coord(lat_rad: f64, lon_rad: f64, zoom: u8) -> (f64, f64) {
+    // scale factor
+    let n = 2_f64.powi(zoom as i32);
+
+    let lon_deg = lon_rad * 180_f64 / PI;
+
+    let x = (lon_deg + 180_f64) / 360_f64 * n;
+    let y = (1_f64 - lat_rad.tan().asinh() / PI) / 2_f64 * n;
+
+    (x, y)
+}
+
+fn header_str(headers: &reqwest::header::HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    headers.get(name)?.to_str().ok().map(String::from)
+}
+
+/// Extracts the `host[:port]` portion of a URL without pulling in a full URL
+/// parser, since we only ever deal with the `http(s)://host/path` shape.
+fn host_of(url: &str) -> &str {
+    let after_scheme = url.splitn(2, "://").nth(1).unwrap_or(url);
+    after_scheme.split('/').next().unwrap_or(after_scheme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_of_strips_scheme_and_path() {
+        assert_eq!(host_of("https://a.tile.osm.org/0/0/0.png"), "a.tile.osm.org");
+        assert_eq!(host_of("http://tile.osm.org:8080/0/0/0.png"), "tile.osm.org:8080");
+        assert_eq!(host_of("https://tile.osm.org"), "tile.osm.org");
     }
 }
@@ -0,0 +1,64 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::time::delay_for;
+
+/// A generic shared token bucket, refilling continuously at `rate` tokens
+/// per second up to a one-second burst capacity, with [`acquire`](Self::acquire)
+/// blocking until enough tokens are available.
+///
+/// Shared by [`crate::bandwidth::Throttle`] (bytes/sec) and
+/// [`crate::rate_limit::RateLimiter`] (requests/sec), which differ only in
+/// the unit `rate` and `acquire`'s `amount` are counted in.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    rate: f64,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a bucket that refills at `rate` tokens/sec, starting full.
+    pub(crate) fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            state: Mutex::new(State {
+                tokens: rate,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until `amount` tokens' worth of budget is available, then
+    /// consumes it.
+    pub(crate) async fn acquire(&self, amount: f64) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+
+                let elapsed = state.last_refill.elapsed().as_secs_f64();
+                state.last_refill = Instant::now();
+                state.tokens = (state.tokens + elapsed * self.rate).min(self.rate.max(amount));
+
+                if state.tokens >= amount {
+                    state.tokens -= amount;
+                    None
+                } else {
+                    let deficit = amount - state.tokens;
+                    Some(Duration::from_secs_f64(deficit / self.rate))
+                }
+            };
+
+            match wait {
+                Some(d) => delay_for(d).await,
+                None => return,
+            }
+        }
+    }
+}
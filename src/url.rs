@@ -1,4 +1,4 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use maplit::hashmap;
 use std::{cell::RefCell, fmt, sync::Mutex};
 use strfmt::strfmt;
@@ -7,16 +7,52 @@ use crate::tile::Tile;
 
 const OSM_SERVERS: &[&str] = &["a", "b", "c"];
 
+/// The row-addressing scheme a [`UrlFormat`]'s template uses, detected once
+/// from whichever of `{y}`, `{-y}`, `{q}` appears in it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Scheme {
+    /// Plain XYZ, row increasing southward from the top: `{y}`.
+    Xyz,
+    /// TMS, row increasing northward from the bottom: `{-y}`.
+    Tms,
+    /// Bing/Microsoft quadkey, `{q}`, which folds `x`, `y` and `z` into a
+    /// single base-4 string and so stands on its own.
+    Quadkey,
+}
+
+impl Scheme {
+    /// Detects which scheme a template uses, requiring exactly one of the
+    /// three row placeholders so a template can't mix addressing schemes.
+    fn detect(format_str: &str) -> Result<Self> {
+        let has_xyz = format_str.contains("{y}");
+        let has_tms = format_str.contains("{-y}");
+        let has_quadkey = format_str.contains("{q}");
+
+        match (has_xyz, has_tms, has_quadkey) {
+            (true, false, false) => Ok(Scheme::Xyz),
+            (false, true, false) => Ok(Scheme::Tms),
+            (false, false, true) => Ok(Scheme::Quadkey),
+            _ => Err(anyhow!(
+                "URL template must use exactly one of `{{y}}` (XYZ), `{{-y}}` (TMS) or `{{q}}` (quadkey)"
+            )),
+        }
+    }
+}
+
 /// A tile URL formatter. Tile URLs are allowed to contain any of
 /// the following tokens:
 ///
 /// - `x`: the X coordinate of the tile
-/// - `y`: the Y coordinate of the tile
+/// - `y`: the XYZ Y coordinate of the tile, increasing southward
+/// - `-y`: the TMS Y coordinate of the tile, increasing northward (`2^z - 1 - y`)
 /// - `z`: the Z coordinate (zoom level) of the tile
+/// - `q`: the Bing/Microsoft quadkey encoding `x`, `y` and `z` as one base-4 string
 /// - `s`: the subdomain, sequentially chosen from `["a", "b", "c"]`
 ///
-/// Subdomains (`{s}`) aren't required, but they help with parallel
-/// downloads. Format tokens should be surrounded by curly brackets.
+/// A template must use exactly one of `y`, `-y` or `q` to address the tile
+/// row; mixing them is rejected. Subdomains (`{s}`) aren't required, but
+/// they help with parallel downloads. Format tokens should be surrounded by
+/// curly brackets.
 ///
 /// # Example
 /// ```rust
@@ -25,7 +61,7 @@ const OSM_SERVERS: &[&str] = &["a", "b", "c"];
 ///
 /// # fn main() -> Result<()> {
 /// let format_str = "https://{s}.foo.com/{x}/{y}/{z}.png".to_owned();
-/// let url_fmt = UrlFormat::from_string(format_str);
+/// let url_fmt = UrlFormat::from_string(format_str)?;
 /// let tile = Tile::new(1, 2, 3);
 ///
 /// assert_eq!(url_fmt.tile_url(&tile)?, "https://a.foo.com/1/2/3.png");
@@ -40,15 +76,22 @@ const OSM_SERVERS: &[&str] = &["a", "b", "c"];
 pub struct UrlFormat {
     inc: Mutex<RefCell<u8>>,
     format_str: String,
+    scheme: Scheme,
 }
 
 impl UrlFormat {
     /// Create a new URL formatter from a given format string.
-    pub fn from_string(format_str: String) -> Self {
-        Self {
+    ///
+    /// # Errors
+    /// Fails if the template doesn't use exactly one of `{y}`, `{-y}` or `{q}`.
+    pub fn from_string(format_str: String) -> Result<Self> {
+        let scheme = Scheme::detect(&format_str)?;
+
+        Ok(Self {
             inc: Mutex::new(RefCell::new(0)),
             format_str,
-        }
+            scheme,
+        })
     }
 
     fn get_inc(&self) -> u8 {
@@ -64,17 +107,43 @@ impl UrlFormat {
     /// Get a formatted URL for the given tile.
     pub fn tile_url(&self, tile: &Tile) -> Result<String> {
         let inc = self.get_inc() as usize;
-        let vars = hashmap! {
+        let mut vars = hashmap! {
             "s".to_owned() => OSM_SERVERS[inc % OSM_SERVERS.len()].to_owned(),
             "x".to_owned() => tile.x.to_string(),
-            "y".to_owned() => tile.y.to_string(),
             "z".to_owned() => tile.z.to_string(),
         };
 
+        match self.scheme {
+            Scheme::Xyz => {
+                vars.insert("y".to_owned(), tile.y.to_string());
+            }
+            Scheme::Tms => {
+                let tms_y = (1usize << tile.z) - 1 - tile.y;
+                vars.insert("-y".to_owned(), tms_y.to_string());
+            }
+            Scheme::Quadkey => {
+                vars.insert("q".to_owned(), quadkey(tile));
+            }
+        }
+
         strfmt(&self.format_str, &vars).context("failed formatting URL")
     }
 }
 
+/// Encodes a tile's `x`, `y` and `z` as a Bing/Microsoft quadkey: for each
+/// zoom level `i` from `z` down to `1`, one base-4 digit combining the `i`th
+/// bit of `x` and `y`.
+fn quadkey(tile: &Tile) -> String {
+    (1..=tile.z)
+        .rev()
+        .map(|i| {
+            let shift = i as u32 - 1;
+            let digit = ((tile.x >> shift) & 1) + 2 * ((tile.y >> shift) & 1);
+            std::char::from_digit(digit as u32, 4).unwrap()
+        })
+        .collect()
+}
+
 impl PartialEq for UrlFormat {
     fn eq(&self, other: &Self) -> bool {
         self.format_str == other.format_str
@@ -88,3 +157,33 @@ impl fmt::Debug for UrlFormat {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quadkey_matches_bing_reference_example() {
+        // https://learn.microsoft.com/en-us/bingmaps/articles/bing-maps-tile-system
+        assert_eq!(quadkey(&Tile::new(3, 5, 3)), "213");
+    }
+
+    #[test]
+    fn quadkey_top_left_tile_is_all_zeroes() {
+        assert_eq!(quadkey(&Tile::new(0, 0, 4)), "0000");
+    }
+
+    #[test]
+    fn tms_row_flips_xyz_row() {
+        let url_fmt = UrlFormat::from_string("https://tile/{z}/{x}/{-y}.png".to_owned()).unwrap();
+        // z=3 has 8 rows (0..=7); XYZ row 1 is TMS row 6
+        let tile = Tile::new(0, 1, 3);
+
+        assert_eq!(url_fmt.tile_url(&tile).unwrap(), "https://tile/3/0/6.png");
+    }
+
+    #[test]
+    fn rejects_mixed_row_placeholders() {
+        assert!(Scheme::detect("{x}/{y}/{-y}").is_err());
+    }
+}